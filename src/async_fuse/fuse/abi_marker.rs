@@ -103,6 +103,7 @@ mark_sized_types!(@kernel size_check: check_common,
     FuseLockOut,
     FuseAccessIn,
     FuseInitIn,
+    FuseInitInExt,
     FuseInitOut,
     FuseInterruptIn,
     FuseBMapIn,
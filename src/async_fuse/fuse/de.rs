@@ -15,6 +15,8 @@ use super::context::ProtoVersion;
 pub struct Deserializer<'b> {
     /// inner bytes
     bytes: &'b [u8],
+    /// Whether names must be valid UTF-8
+    utf8_names: bool,
 }
 
 /// Types which can be decoded from bytes
@@ -52,6 +54,31 @@ pub enum DeserializeError {
     #[error("InvalidValue")]
     InvalidValue,
 
+    /// The request is shorter than the fixed argument of its opcode
+    #[error(
+        "ShortRead OpCode={code}, expected at least {expected} bytes of arguments, got {actual}"
+    )]
+    ShortRead {
+        /// The opcode
+        code: u32,
+        /// The minimum argument length of the opcode
+        expected: usize,
+        /// The argument length actually received
+        actual: usize,
+    },
+
+    /// The first of two consecutive nul-terminated names is missing
+    #[error("MissingFirstName")]
+    MissingFirstName,
+
+    /// The first name is present but the second one is missing or truncated
+    #[error("MissingSecondName")]
+    MissingSecondName,
+
+    /// A name is not valid UTF-8
+    #[error("NonUtf8Name")]
+    NonUtf8Name,
+
     /// An unknown opcode of FUSE request found
     #[error("Unknown OpCode={code}")]
     UnknownOpCode {
@@ -97,7 +124,17 @@ fn check_size(len: usize, need: usize) -> Result<(), DeserializeError> {
 impl<'b> Deserializer<'b> {
     /// Create `Deserializer`
     pub const fn new(bytes: &'b [u8]) -> Deserializer<'b> {
-        Self { bytes }
+        Self {
+            bytes,
+            utf8_names: false,
+        }
+    }
+
+    /// Require the names fetched by [`Deserializer::fetch_name`] to be valid
+    /// UTF-8
+    pub const fn utf8_names(mut self) -> Deserializer<'b> {
+        self.utf8_names = true;
+        self
     }
 
     /// pop some bytes without length check
@@ -188,7 +225,6 @@ impl<'b> Deserializer<'b> {
         unsafe { Ok(self.pop_bytes_unchecked(strlen)) }
     }
 
-    #[allow(dead_code)]
     /// Fetch some nul-terminated bytes and return an `OsStr` without the nul
     /// byte.
     pub fn fetch_os_str(&mut self) -> Result<&'b OsStr, DeserializeError> {
@@ -206,6 +242,9 @@ impl<'b> Deserializer<'b> {
 
     /// Fetch some nul-terminated bytes and return an `str` without the nul
     /// byte.
+    ///
+    /// Returns `NonUtf8Name` if the bytes are not valid UTF-8, they are still
+    /// consumed.
     pub fn fetch_str(&mut self) -> Result<&'b str, DeserializeError> {
         let bytes_with_nul = self.fetch_c_str()?;
 
@@ -214,8 +253,50 @@ impl<'b> Deserializer<'b> {
             bytes_with_nul.get_unchecked(..len)
         };
 
-        Ok(std::str::from_utf8(bytes_without_nul)
-            .unwrap_or_else(|e| panic!("failed to convert to utf8 string, error is {e:?}")))
+        std::str::from_utf8(bytes_without_nul).map_err(|e| {
+            trace!("failed to convert to utf8 string, the error is: {e}");
+            DeserializeError::NonUtf8Name
+        })
+    }
+
+    /// Fetch a nul-terminated name and return it without the nul byte.
+    ///
+    /// Any bytes are accepted as Linux does, unless UTF-8 names are required,
+    /// in which case `NonUtf8Name` is returned for the others. The bytes are
+    /// consumed either way.
+    pub fn fetch_name(&mut self) -> Result<&'b OsStr, DeserializeError> {
+        if self.utf8_names {
+            self.fetch_str().map(OsStr::new)
+        } else {
+            self.fetch_os_str()
+        }
+    }
+
+    /// Fetch two consecutive nul-terminated names, as sent with `SYMLINK` and
+    /// `RENAME`.
+    ///
+    /// Returns `MissingFirstName` if there is no nul-terminated name at all,
+    /// `MissingSecondName` if the first name consumes all the remaining bytes
+    /// or the second one is not nul-terminated, and `NonUtf8Name` if UTF-8
+    /// names are required and either name is not valid UTF-8.
+    pub fn fetch_two_names(&mut self) -> Result<(&'b OsStr, &'b OsStr), DeserializeError> {
+        let first = self.fetch_name().map_err(|e| {
+            trace!("failed to fetch the first name, the error is: {e}");
+            if e == DeserializeError::NonUtf8Name {
+                e
+            } else {
+                DeserializeError::MissingFirstName
+            }
+        })?;
+        let second = self.fetch_name().map_err(|e| {
+            trace!("failed to fetch the second name, the error is: {e}");
+            if e == DeserializeError::NonUtf8Name {
+                e
+            } else {
+                DeserializeError::MissingSecondName
+            }
+        })?;
+        Ok((first, second))
     }
 
     /// Returns `TooMuchData` if the bytes is not completely consumed
@@ -235,6 +316,9 @@ impl<'b> Deserializer<'b> {
 #[cfg(test)]
 #[allow(clippy::host_endian_bytes)] // For test only
 mod tests {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
     use aligned_utils::stack::Align8;
 
     use super::Deserializer;
@@ -342,4 +426,89 @@ mod tests {
         );
         assert_eq!(de.bytes.len(), 0);
     }
+
+    #[test]
+    fn fetch_two_names() {
+        let buf: [u8; 12] = *b"hello\0world\0";
+        let mut de = Deserializer::new(&buf);
+        assert_eq!(
+            de.fetch_two_names()
+                .unwrap_or_else(|err| panic!("failed to fetch two names, the error is: {err}")),
+            (OsStr::new("hello"), OsStr::new("world"))
+        );
+        assert_eq!(de.bytes.len(), 0);
+
+        let buf: [u8; 10] = *b"hello\0worl";
+        let mut de = Deserializer::new(&buf);
+        assert_eq!(
+            de.fetch_two_names(),
+            Err(super::DeserializeError::MissingSecondName)
+        );
+
+        let buf: [u8; 6] = *b"hello\0";
+        let mut de = Deserializer::new(&buf);
+        assert_eq!(
+            de.fetch_two_names(),
+            Err(super::DeserializeError::MissingSecondName)
+        );
+
+        let buf: [u8; 5] = *b"hello";
+        let mut de = Deserializer::new(&buf);
+        assert_eq!(
+            de.fetch_two_names(),
+            Err(super::DeserializeError::MissingFirstName)
+        );
+
+        let buf: [u8; 12] = *b"hello\0w\xffrld\0";
+        let mut de = Deserializer::new(&buf);
+        assert_eq!(
+            de.fetch_two_names()
+                .unwrap_or_else(|err| panic!("failed to fetch two names, the error is: {err}")),
+            (OsStr::new("hello"), OsStr::from_bytes(b"w\xffrld"))
+        );
+        let mut de = Deserializer::new(&buf).utf8_names();
+        assert_eq!(
+            de.fetch_two_names(),
+            Err(super::DeserializeError::NonUtf8Name)
+        );
+    }
+
+    #[test]
+    fn fetch_name() {
+        let buf: [u8; 12] = *b"h\xc3\xa9llo\0w\xffld\0";
+        let mut de = Deserializer::new(&buf);
+        assert_eq!(
+            de.fetch_name()
+                .unwrap_or_else(|err| panic!("failed to fetch a name, the error is: {err}")),
+            "h\u{e9}llo"
+        );
+        assert_eq!(
+            de.fetch_name()
+                .unwrap_or_else(|err| panic!("failed to fetch a name, the error is: {err}")),
+            OsStr::from_bytes(b"w\xffld")
+        );
+        assert_eq!(de.bytes.len(), 0);
+
+        let mut de = Deserializer::new(&buf).utf8_names();
+        assert_eq!(
+            de.fetch_name()
+                .unwrap_or_else(|err| panic!("failed to fetch a name, the error is: {err}")),
+            "h\u{e9}llo"
+        );
+        assert_eq!(de.fetch_name(), Err(super::DeserializeError::NonUtf8Name));
+        assert_eq!(de.bytes.len(), 0);
+    }
+
+    #[test]
+    fn fetch_str() {
+        let buf: [u8; 12] = *b"h\xc3\xa9llo\0w\xffld\0";
+        let mut de = Deserializer::new(&buf);
+        assert_eq!(
+            de.fetch_str()
+                .unwrap_or_else(|err| panic!("failed to fetch a string, the error is: {err}")),
+            "h\u{e9}llo"
+        );
+        assert_eq!(de.fetch_str(), Err(super::DeserializeError::NonUtf8Name));
+        assert_eq!(de.bytes.len(), 0);
+    }
 }
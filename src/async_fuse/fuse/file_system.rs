@@ -14,8 +14,9 @@ use crate::async_fuse::memfs::{CreateParam, FileLockParam, RenameParam, SetAttrP
 /// FUSE filesystem trait
 #[async_trait]
 pub trait FileSystem {
-    /// Initialize filesystem
-    async fn init(&self, req: &Request<'_>) -> nix::Result<()>;
+    /// Initialize filesystem, `max_write` is the max size of write requests
+    /// negotiated with the kernel
+    async fn init(&self, req: &Request<'_>, max_write: u32) -> nix::Result<()>;
 
     /// Clean up filesystem
     async fn destroy(&self, req: &Request<'_>);
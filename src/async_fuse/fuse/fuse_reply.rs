@@ -20,9 +20,12 @@ use nix::sys::stat::SFlag;
 use tracing::debug;
 
 use super::abi_marker;
+#[cfg(feature = "abi-7-28")]
+use super::protocol::FUSE_MAX_PAGES;
 use super::protocol::{
     FuseAttr, FuseAttrOut, FuseBMapOut, FuseDirEnt, FuseEntryOut, FuseFileLock, FuseGetXAttrOut,
-    FuseInitOut, FuseKStatFs, FuseLockOut, FuseOpenOut, FuseOutHeader, FuseStatFsOut, FuseWriteOut,
+    FuseInitIn, FuseInitOut, FuseKStatFs, FuseLockOut, FuseOpenOut, FuseOutHeader, FuseStatFsOut,
+    FuseWriteOut, FUSE_KERNEL_MINOR_VERSION, FUSE_KERNEL_VERSION,
 };
 #[cfg(feature = "abi-7-18")]
 use super::protocol::{FuseNotifyCode::FUSE_NOTIFY_DELETE, FuseNotifyDeleteOut};
@@ -381,6 +384,183 @@ impl ReplyInit<'_> {
     }
 }
 
+/// Build the bytes of an error reply to the request `unique`, which is a bare
+/// `fuse_out_header` carrying the negated `errno`
+#[allow(dead_code)]
+#[inline]
+#[must_use]
+pub fn error_reply(unique: u64, errno: i32) -> Vec<u8> {
+    let header = FuseOutHeader {
+        len: mem::size_of::<FuseOutHeader>().cast(),
+        // FUSE requires the error number to be negative
+        error: errno.overflow_neg(),
+        unique,
+    };
+    abi_marker::as_abi_bytes(&header).to_vec()
+}
+
+/// The minimum FUSE minor version supported, ABI versions before 7.8 are
+/// rejected
+pub const FUSE_MIN_KERNEL_MINOR_VERSION: u32 = 8;
+
+/// The page size the kernel counts request pages in
+const FUSE_PAGE_SIZE: u32 = 4096;
+
+/// The minimum max write size, the kernel always allows writes of a page
+pub const FUSE_MIN_MAX_WRITE: u32 = FUSE_PAGE_SIZE;
+
+/// The max write size of kernels not negotiating `FUSE_MAX_PAGES`, which cap
+/// requests at 32 pages
+pub const FUSE_DEFAULT_MAX_WRITE: u32 = 32 * FUSE_PAGE_SIZE;
+
+/// The max write size of kernels negotiating `FUSE_MAX_PAGES`, which cap
+/// requests at 256 pages
+pub const FUSE_MAX_MAX_WRITE: u32 = 256 * FUSE_PAGE_SIZE;
+
+/// Builder of the FUSE init response negotiated from the kernel init request
+#[derive(Debug, Clone, Copy)]
+pub struct InitReplyBuilder {
+    /// The capability flags the filesystem wants
+    flags: u32,
+    /// The max size of write requests from the kernel
+    max_write: u32,
+    /// The max readahead size, the kernel proposed one is used if `None`
+    max_readahead: Option<u32>,
+    /// Max background pending requests under processing
+    #[cfg(feature = "abi-7-13")]
+    max_background: u16,
+    /// The number of pending requests above which the filesystem is
+    /// "congested"
+    #[cfg(feature = "abi-7-13")]
+    congestion_threshold: u16,
+}
+
+impl InitReplyBuilder {
+    /// Create a builder with the capability flags the filesystem wants, the
+    /// flags not supported by the kernel are dropped when building.
+    #[inline]
+    #[must_use]
+    pub const fn new(flags: u32) -> Self {
+        Self {
+            flags,
+            max_write: 4096,
+            max_readahead: None,
+            #[cfg(feature = "abi-7-13")]
+            max_background: 0,
+            #[cfg(feature = "abi-7-13")]
+            congestion_threshold: 0,
+        }
+    }
+
+    /// Set the max size of write requests from the kernel, e.g. to align
+    /// writes to the part size of the backend.
+    ///
+    /// It is clamped to [`FUSE_MIN_MAX_WRITE`] and [`FUSE_MAX_MAX_WRITE`], and
+    /// further capped to [`FUSE_DEFAULT_MAX_WRITE`] when building if the
+    /// kernel can not send larger requests.
+    #[inline]
+    #[must_use]
+    pub const fn max_write(mut self, max_write: u32) -> Self {
+        self.max_write = if max_write < FUSE_MIN_MAX_WRITE {
+            FUSE_MIN_MAX_WRITE
+        } else if max_write > FUSE_MAX_MAX_WRITE {
+            FUSE_MAX_MAX_WRITE
+        } else {
+            max_write
+        };
+        self
+    }
+
+    /// Set the max readahead size, it is capped by the one proposed by the
+    /// kernel
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn max_readahead(mut self, max_readahead: u32) -> Self {
+        self.max_readahead = Some(max_readahead);
+        self
+    }
+
+    /// Set the max background pending requests under processing
+    #[allow(unused_variables, unused_mut)]
+    #[inline]
+    #[must_use]
+    pub const fn max_background(mut self, max_background: u16) -> Self {
+        #[cfg(feature = "abi-7-13")]
+        {
+            self.max_background = max_background;
+        }
+        self
+    }
+
+    /// Set the congestion threshold of pending requests
+    #[allow(unused_variables, unused_mut)]
+    #[inline]
+    #[must_use]
+    pub const fn congestion_threshold(mut self, congestion_threshold: u16) -> Self {
+        #[cfg(feature = "abi-7-13")]
+        {
+            self.congestion_threshold = congestion_threshold;
+        }
+        self
+    }
+
+    /// Build the init response to the kernel init request `arg`.
+    ///
+    /// The response always carries the ABI version this crate is built with,
+    /// the kernel takes the smaller one of the two as the negotiated version.
+    /// The capability flags are the intersection of the desired flags and the
+    /// ones offered by the kernel. The max write size is capped to
+    /// [`FUSE_DEFAULT_MAX_WRITE`] unless the kernel offers `FUSE_MAX_PAGES`,
+    /// in which case the flag is set along with the matching `max_pages`.
+    ///
+    /// Returns `EPROTO` if the kernel ABI version is lower than 7.8.
+    #[inline]
+    pub fn build(&self, arg: &FuseInitIn) -> nix::Result<FuseInitOut> {
+        if arg.major < FUSE_KERNEL_VERSION
+            || (arg.major == FUSE_KERNEL_VERSION && arg.minor < FUSE_MIN_KERNEL_MINOR_VERSION)
+        {
+            return Err(Errno::EPROTO);
+        }
+
+        let max_readahead = self
+            .max_readahead
+            .map_or(arg.max_readahead, |v| v.min(arg.max_readahead));
+        #[allow(unused_mut)]
+        let mut flags = arg.flags & self.flags;
+        #[allow(unused_mut)]
+        let mut max_write = self.max_write.min(FUSE_DEFAULT_MAX_WRITE);
+        #[cfg(feature = "abi-7-28")]
+        if arg.flags & FUSE_MAX_PAGES != 0 {
+            flags |= FUSE_MAX_PAGES;
+            max_write = self.max_write;
+        }
+        Ok(FuseInitOut {
+            major: FUSE_KERNEL_VERSION,
+            minor: FUSE_KERNEL_MINOR_VERSION,
+            max_readahead,
+            flags,
+            #[cfg(not(feature = "abi-7-13"))]
+            unused: 0,
+            #[cfg(feature = "abi-7-13")]
+            max_background: self.max_background,
+            #[cfg(feature = "abi-7-13")]
+            congestion_threshold: self.congestion_threshold,
+            max_write,
+            #[cfg(feature = "abi-7-23")]
+            time_gran: 1, // TODO: set time_gran
+            #[cfg(all(feature = "abi-7-23", not(feature = "abi-7-28")))]
+            unused: [0; 9],
+            #[cfg(feature = "abi-7-28")]
+            max_pages: max_write.div_ceil(FUSE_PAGE_SIZE).cast(),
+            #[cfg(feature = "abi-7-28")]
+            padding: 0,
+            #[cfg(feature = "abi-7-28")]
+            unused: [0; 8],
+        })
+    }
+}
+
 /// FUSE empty response
 #[derive(Debug)]
 pub struct ReplyEmpty<'a> {
@@ -782,19 +962,29 @@ impl<'a> FuseDeleteNotification<'a> {
 #[cfg(test)]
 mod test {
     use std::fs::File;
+    use std::mem;
     use std::os::unix::io::FromRawFd;
     use std::time::Duration;
 
     use aligned_utils::bytes::AlignedBytes;
     use anyhow::Context;
+    use clippy_utilities::Cast;
     use nix::fcntl::{self, OFlag};
     use nix::sys::stat::Mode;
     use nix::unistd;
     use tokio::io::{AsyncReadExt, AsyncSeekExt};
 
+    use nix::errno::Errno;
+
     use super::super::de::Deserializer;
-    use super::super::protocol::{FuseAttr, FuseAttrOut, FuseOutHeader};
-    use super::ReplyAttr;
+    use super::super::protocol::{
+        FuseAttr, FuseAttrOut, FuseBMapOut, FuseInitIn, FuseOutHeader, FUSE_ASYNC_READ,
+        FUSE_KERNEL_MINOR_VERSION, FUSE_KERNEL_VERSION, FUSE_POSIX_LOCKS,
+    };
+    use super::{
+        error_reply, InitReplyBuilder, ReplyAttr, ReplyBMap, FUSE_DEFAULT_MAX_WRITE,
+        FUSE_MAX_MAX_WRITE, FUSE_MIN_MAX_WRITE,
+    };
 
     #[test]
     fn test_slice() {
@@ -815,6 +1005,119 @@ mod test {
         println!("{l1:?}");
         println!("{v1:?}");
     }
+
+    #[test]
+    fn test_init_negotiation() {
+        // The INIT request from a 7.31 kernel offering async read and big writes
+        let arg = FuseInitIn {
+            major: 7,
+            minor: 31,
+            max_readahead: 0x2_0000,
+            flags: FUSE_ASYNC_READ | (1 << 5_i32), // FUSE_BIG_WRITES
+        };
+        let builder = InitReplyBuilder::new(FUSE_ASYNC_READ | FUSE_POSIX_LOCKS)
+            .max_write(128 * 1024)
+            .max_readahead(0x1_0000)
+            .max_background(10)
+            .congestion_threshold(8);
+        let out = builder
+            .build(&arg)
+            .unwrap_or_else(|err| panic!("failed to negotiate with a 7.31 kernel: {err}"));
+        assert_eq!(out.major, FUSE_KERNEL_VERSION);
+        assert_eq!(out.minor, FUSE_KERNEL_MINOR_VERSION);
+        assert_eq!(out.flags, FUSE_ASYNC_READ);
+        assert_eq!(out.max_readahead, 0x1_0000);
+        assert_eq!(out.max_write, 128 * 1024);
+        #[cfg(feature = "abi-7-13")]
+        {
+            assert_eq!(out.max_background, 10);
+            assert_eq!(out.congestion_threshold, 8);
+        }
+
+        // The max readahead can not exceed the kernel proposed one
+        let out = InitReplyBuilder::new(0)
+            .max_readahead(0x10_0000)
+            .build(&arg)
+            .unwrap_or_else(|err| panic!("failed to negotiate with a 7.31 kernel: {err}"));
+        assert_eq!(out.max_readahead, 0x2_0000);
+        assert_eq!(out.flags, 0);
+
+        // ABI versions before 7.8 are rejected
+        for &(major, minor) in &[(7, 7), (6, 31)] {
+            let arg = FuseInitIn {
+                major,
+                minor,
+                max_readahead: 0x2_0000,
+                flags: FUSE_ASYNC_READ,
+            };
+            assert_eq!(builder.build(&arg).err(), Some(Errno::EPROTO));
+        }
+    }
+
+    #[test]
+    fn test_max_write_negotiation() {
+        let arg = FuseInitIn {
+            major: 7,
+            minor: 31,
+            max_readahead: 0x2_0000,
+            flags: FUSE_ASYNC_READ,
+        };
+
+        // Clamped to the range the kernel supports
+        for (max_write, expected) in [
+            (0, FUSE_MIN_MAX_WRITE),
+            (64 * 1024, 64 * 1024),
+            (8 * 1024 * 1024, FUSE_MAX_MAX_WRITE),
+        ] {
+            let builder = InitReplyBuilder::new(0).max_write(max_write);
+            assert_eq!(builder.max_write, expected);
+        }
+
+        // Capped without `FUSE_MAX_PAGES`
+        let builder = InitReplyBuilder::new(0).max_write(512 * 1024);
+        let out = builder
+            .build(&arg)
+            .unwrap_or_else(|err| panic!("failed to negotiate with a 7.31 kernel: {err}"));
+        assert_eq!(out.max_write, FUSE_DEFAULT_MAX_WRITE);
+
+        #[cfg(feature = "abi-7-28")]
+        {
+            use super::super::protocol::FUSE_MAX_PAGES;
+
+            let arg = FuseInitIn {
+                flags: FUSE_ASYNC_READ | FUSE_MAX_PAGES,
+                ..arg
+            };
+            let out = builder
+                .build(&arg)
+                .unwrap_or_else(|err| panic!("failed to negotiate with a 7.31 kernel: {err}"));
+            assert_eq!(out.max_write, 512 * 1024);
+            assert_eq!(out.max_pages, 128);
+            assert_eq!(out.flags, FUSE_MAX_PAGES);
+        }
+    }
+
+    #[test]
+    #[allow(clippy::host_endian_bytes)] // For test only
+    fn test_error_reply() {
+        let bytes = error_reply(0xdead_beef, libc::ENOENT);
+        assert_eq!(bytes.len(), mem::size_of::<FuseOutHeader>());
+
+        let field = |range: std::ops::Range<usize>| -> [u8; 4] {
+            bytes
+                .get(range)
+                .and_then(|b| b.try_into().ok())
+                .unwrap_or_else(|| panic!("the reply is too short"))
+        };
+        assert_eq!(u32::from_ne_bytes(field(0..4)), 16);
+        assert_eq!(i32::from_ne_bytes(field(4..8)), -libc::ENOENT);
+        let unique: [u8; 8] = bytes
+            .get(8..16)
+            .and_then(|b| b.try_into().ok())
+            .unwrap_or_else(|| panic!("the reply is too short"));
+        assert_eq!(u64::from_ne_bytes(unique), 0xdead_beef);
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_reply_output() -> anyhow::Result<()> {
         let file_name = "fuse_reply.log";
@@ -895,4 +1198,44 @@ mod test {
         debug_assert_eq!(fao.attr.ctime, c_time);
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reply_bmap() -> anyhow::Result<()> {
+        let file_name = "fuse_reply_bmap.log";
+        let fd = tokio::task::spawn_blocking(move || {
+            fcntl::open(
+                file_name,
+                OFlag::O_CREAT | OFlag::O_TRUNC | OFlag::O_RDWR,
+                Mode::all(),
+            )
+        })
+        .await??;
+        tokio::task::spawn_blocking(move || unistd::unlink(file_name)).await??;
+
+        let unique = 12345;
+        let block = 0x1234_5678;
+        // SAFETY: `fd` is just opened
+        let mut file = unsafe { File::from_raw_fd(fd) };
+        ReplyBMap::new(unique, &mut file).bmap(block).await?;
+
+        std::io::Seek::seek(&mut file, std::io::SeekFrom::Start(0))?;
+        let mut bytes = Vec::new();
+        std::io::Read::read_to_end(&mut file, &mut bytes)?;
+
+        let mut aligned_bytes = AlignedBytes::new_zeroed(bytes.len(), 4096);
+        aligned_bytes.copy_from_slice(&bytes);
+
+        let mut de = Deserializer::new(&aligned_bytes);
+        let foh: &FuseOutHeader = de.fetch_ref().context("failed to fetch FuseOutHeader")?;
+        let fbo: &FuseBMapOut = de.fetch_ref().context("failed to fetch FuseBMapOut")?;
+
+        assert_eq!(foh.unique, unique);
+        assert_eq!(foh.error, 0_i32);
+        assert_eq!(
+            foh.len.cast::<usize>(),
+            mem::size_of::<FuseOutHeader>() + mem::size_of::<FuseBMapOut>()
+        );
+        assert_eq!(fbo.block, block);
+        Ok(())
+    }
 }
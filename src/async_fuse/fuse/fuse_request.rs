@@ -1,7 +1,9 @@
 //! The implementation for FUSE request
 
-use std::fmt;
+use std::ffi::OsStr;
+use std::{fmt, mem};
 
+use bytes::{Bytes, BytesMut};
 use clippy_utilities::Cast;
 use tracing::debug;
 
@@ -13,12 +15,15 @@ use super::protocol::FuseFAllocateIn;
 use super::protocol::FuseRename2In;
 use super::protocol::{
     FuseAccessIn, FuseBMapIn, FuseCopyFileRangeIn, FuseCreateIn, FuseFSyncIn, FuseFlushIn,
-    FuseForgetIn, FuseGetXAttrIn, FuseInHeader, FuseInitIn, FuseInterruptIn, FuseLSeekIn,
-    FuseLinkIn, FuseLockIn, FuseMkDirIn, FuseMkNodIn, FuseOpCode, FuseOpenIn, FuseReadIn,
-    FuseReleaseIn, FuseRenameIn, FuseSetAttrIn, FuseSetXAttrIn, FuseWriteIn,
+    FuseForgetIn, FuseGetXAttrIn, FuseInHeader, FuseInitIn, FuseInitInExt, FuseInterruptIn,
+    FuseLSeekIn, FuseLinkIn, FuseLockIn, FuseMkDirIn, FuseMkNodIn, FuseOpCode, FuseOpenIn,
+    FuseReadIn, FuseReleaseIn, FuseRenameIn, FuseSetAttrIn, FuseSetXAttrIn, FuseWriteIn,
+    SetAttrValid, FATTR_FH, FUSE_INIT_EXT, FUSE_ROOT_ID,
 };
 #[cfg(feature = "abi-7-16")]
 use super::protocol::{FuseBatchForgetIn, FuseForgetOne};
+#[cfg(feature = "abi-7-9")]
+use super::protocol::{FuseGetAttrIn, FUSE_GETATTR_FH};
 #[cfg(feature = "abi-7-11")]
 use super::protocol::{FuseIoCtlIn, FusePollIn};
 
@@ -28,7 +33,7 @@ pub enum Operation<'a> {
     /// FUSE_LOOKUP = 1
     Lookup {
         /// The directory name to look up
-        name: &'a str,
+        name: &'a OsStr,
     },
     /// FUSE_FORGET = 2
     Forget {
@@ -36,7 +41,12 @@ pub enum Operation<'a> {
         arg: &'a FuseForgetIn,
     },
     /// FUSE_GETATTR = 3
-    GetAttr,
+    GetAttr {
+        /// The getattr flags and file handle, absent if the kernel sent no
+        /// argument
+        #[cfg(feature = "abi-7-9")]
+        arg: Option<&'a FuseGetAttrIn>,
+    },
     /// FUSE_SETATTR = 4
     SetAttr {
         /// The attributes to be set
@@ -47,49 +57,49 @@ pub enum Operation<'a> {
     /// FUSE_SYMLINK = 6
     SymLink {
         /// The link name to create
-        name: &'a str,
+        name: &'a OsStr,
         /// The contents of the symbolic link
-        link: &'a str,
+        link: &'a OsStr,
     },
     /// FUSE_MKNOD = 8
     MkNod {
         /// The FUSE mknod request
         arg: &'a FuseMkNodIn,
         /// The file name to create
-        name: &'a str,
+        name: &'a OsStr,
     },
     /// FUSE_MKDIR = 9
     MkDir {
         /// The FUSE mkdir request input
         arg: &'a FuseMkDirIn,
         /// The directory name to create
-        name: &'a str,
+        name: &'a OsStr,
     },
     /// FUSE_UNLINK = 10
     Unlink {
         /// The file name to remove
-        name: &'a str,
+        name: &'a OsStr,
     },
     /// FUSE_RMDIR = 11
     RmDir {
         /// The directory name to remove
-        name: &'a str,
+        name: &'a OsStr,
     },
     /// FUSE_RENAME = 12
     Rename {
         /// The FUSE rename request
         arg: &'a FuseRenameIn,
         /// The old name
-        oldname: &'a str,
+        oldname: &'a OsStr,
         /// The new name
-        newname: &'a str,
+        newname: &'a OsStr,
     },
     /// FUSE_LINK = 13
     Link {
         /// The FUSE link request
         arg: &'a FuseLinkIn,
         /// The new name
-        name: &'a str,
+        name: &'a OsStr,
     },
     /// FUSE_OPEN = 14
     Open {
@@ -125,7 +135,7 @@ pub enum Operation<'a> {
         /// The FUSE set extended attribute request
         arg: &'a FuseSetXAttrIn,
         /// The extended attribute name
-        name: &'a str,
+        name: &'a OsStr,
         /// The extended attribute value
         value: &'a [u8],
     },
@@ -134,7 +144,7 @@ pub enum Operation<'a> {
         /// The FUSE get extended attribute request
         arg: &'a FuseGetXAttrIn,
         /// The extended attribute name
-        name: &'a str,
+        name: &'a OsStr,
     },
     /// FUSE_LISTXATTR = 23
     ListXAttr {
@@ -144,7 +154,7 @@ pub enum Operation<'a> {
     /// FUSE_REMOVEXATTR = 24
     RemoveXAttr {
         /// The name of the extended attribute to remove
-        name: &'a str,
+        name: &'a OsStr,
     },
     /// FUSE_FLUSH = 25
     Flush {
@@ -155,6 +165,8 @@ pub enum Operation<'a> {
     Init {
         /// The FUSE init request
         arg: &'a FuseInitIn,
+        /// The extended FUSE init request, only sent by ABI 7.36 and later
+        ext: Option<&'a FuseInitInExt>,
     },
     /// FUSE_OPENDIR = 27
     OpenDir {
@@ -201,7 +213,7 @@ pub enum Operation<'a> {
         /// The FUSE create request
         arg: &'a FuseCreateIn,
         /// The file name to create
-        name: &'a str,
+        name: &'a OsStr,
     },
     /// FUSE_INTERRUPT = 36
     Interrupt {
@@ -267,9 +279,9 @@ pub enum Operation<'a> {
         /// The FUSE rename2 request
         arg: &'a FuseRename2In,
         /// The old file name
-        oldname: &'a str,
+        oldname: &'a OsStr,
         /// The new file name
-        newname: &'a str,
+        newname: &'a OsStr,
     },
     /// FUSE_LSEEK = 46,
     // #[cfg(feature = "abi-7-24")]
@@ -289,6 +301,14 @@ pub enum Operation<'a> {
         /// The CUSE init request
         arg: &'a FuseInitIn,
     },
+    /// An opcode unknown to this crate, only built by
+    /// [`Request::new_tolerant`]
+    Unknown {
+        /// The unknown opcode
+        opcode: u32,
+        /// The raw request body after the header
+        data: &'a [u8],
+    },
 }
 
 impl<'a> Operation<'a> {
@@ -299,105 +319,67 @@ impl<'a> Operation<'a> {
         data: &mut Deserializer<'a>,
         #[allow(unused_variables)] proto_version: ProtoVersion,
     ) -> Result<Self, DeserializeError> {
-        let opcode = match n {
-            1 => FuseOpCode::FUSE_LOOKUP,
-            2 => FuseOpCode::FUSE_FORGET,
-            3 => FuseOpCode::FUSE_GETATTR,
-            4 => FuseOpCode::FUSE_SETATTR,
-            5 => FuseOpCode::FUSE_READLINK,
-            6 => FuseOpCode::FUSE_SYMLINK,
-            8 => FuseOpCode::FUSE_MKNOD,
-            9 => FuseOpCode::FUSE_MKDIR,
-            10 => FuseOpCode::FUSE_UNLINK,
-            11 => FuseOpCode::FUSE_RMDIR,
-            12 => FuseOpCode::FUSE_RENAME,
-            13 => FuseOpCode::FUSE_LINK,
-            14 => FuseOpCode::FUSE_OPEN,
-            15 => FuseOpCode::FUSE_READ,
-            16 => FuseOpCode::FUSE_WRITE,
-            17 => FuseOpCode::FUSE_STATFS,
-            18 => FuseOpCode::FUSE_RELEASE,
-            20 => FuseOpCode::FUSE_FSYNC,
-            21 => FuseOpCode::FUSE_SETXATTR,
-            22 => FuseOpCode::FUSE_GETXATTR,
-            23 => FuseOpCode::FUSE_LISTXATTR,
-            24 => FuseOpCode::FUSE_REMOVEXATTR,
-            25 => FuseOpCode::FUSE_FLUSH,
-            26 => FuseOpCode::FUSE_INIT,
-            27 => FuseOpCode::FUSE_OPENDIR,
-            28 => FuseOpCode::FUSE_READDIR,
-            29 => FuseOpCode::FUSE_RELEASEDIR,
-            30 => FuseOpCode::FUSE_FSYNCDIR,
-            31 => FuseOpCode::FUSE_GETLK,
-            32 => FuseOpCode::FUSE_SETLK,
-            33 => FuseOpCode::FUSE_SETLKW,
-            34 => FuseOpCode::FUSE_ACCESS,
-            35 => FuseOpCode::FUSE_CREATE,
-            36 => FuseOpCode::FUSE_INTERRUPT,
-            37 => FuseOpCode::FUSE_BMAP,
-            38 => FuseOpCode::FUSE_DESTROY,
-            #[cfg(feature = "abi-7-11")]
-            39 => FuseOpCode::FUSE_IOCTL,
-            #[cfg(feature = "abi-7-11")]
-            40 => FuseOpCode::FUSE_POLL,
-            #[cfg(feature = "abi-7-15")]
-            41 => FuseOpCode::FUSE_NOTIFY_REPLY,
-            #[cfg(feature = "abi-7-16")]
-            42 => FuseOpCode::FUSE_BATCH_FORGET,
-            #[cfg(feature = "abi-7-19")]
-            43 => FuseOpCode::FUSE_FALLOCATE,
-            #[cfg(feature = "abi-7-21")]
-            44 => FuseOpCode::FUSE_READDIRPLUS,
-            #[cfg(feature = "abi-7-23")]
-            45 => FuseOpCode::FUSE_RENAME2,
-            // #[cfg(feature = "abi-7-24")]
-            46 => FuseOpCode::FUSE_LSEEK,
-            // #[cfg(feature = "abi-7-28")]
-            47 => FuseOpCode::FUSE_COPY_FILE_RANGE,
-            #[cfg(feature = "abi-7-11")]
-            4096 => FuseOpCode::CUSE_INIT,
-
-            code => return Err(DeserializeError::UnknownOpCode { code, unique: None }),
-        };
+        let opcode = decode_opcode(n)?;
+
+        let expected = min_arg_len(&opcode);
+        let actual = data.remaining_len();
+        if actual < expected {
+            return Err(DeserializeError::ShortRead {
+                code: n,
+                expected,
+                actual,
+            });
+        }
 
         Ok(match opcode {
             FuseOpCode::FUSE_LOOKUP => Operation::Lookup {
-                name: data.fetch_str()?,
+                name: data.fetch_name()?,
             },
             FuseOpCode::FUSE_FORGET => Operation::Forget {
                 arg: data.fetch_ref()?,
             },
-            FuseOpCode::FUSE_GETATTR => Operation::GetAttr,
+            FuseOpCode::FUSE_GETATTR => Operation::GetAttr {
+                #[cfg(feature = "abi-7-9")]
+                arg: if data.remaining_len() >= mem::size_of::<FuseGetAttrIn>() {
+                    Some(data.fetch_ref()?)
+                } else {
+                    None
+                },
+            },
             FuseOpCode::FUSE_SETATTR => Operation::SetAttr {
                 arg: data.fetch_ref()?,
             },
             FuseOpCode::FUSE_READLINK => Operation::ReadLink,
-            FuseOpCode::FUSE_SYMLINK => Operation::SymLink {
-                name: data.fetch_str()?,
-                link: data.fetch_str()?,
-            },
+            FuseOpCode::FUSE_SYMLINK => {
+                let (name, link) = data.fetch_two_names()?;
+                Operation::SymLink { name, link }
+            }
             FuseOpCode::FUSE_MKNOD => Operation::MkNod {
                 arg: data.fetch_ref()?,
-                name: data.fetch_str()?,
+                name: data.fetch_name()?,
             },
             FuseOpCode::FUSE_MKDIR => Operation::MkDir {
                 arg: data.fetch_ref()?,
-                name: data.fetch_str()?,
+                name: data.fetch_name()?,
             },
             FuseOpCode::FUSE_UNLINK => Operation::Unlink {
-                name: data.fetch_str()?,
+                name: data.fetch_name()?,
             },
             FuseOpCode::FUSE_RMDIR => Operation::RmDir {
-                name: data.fetch_str()?,
-            },
-            FuseOpCode::FUSE_RENAME => Operation::Rename {
-                arg: data.fetch_ref()?,
-                oldname: data.fetch_str()?,
-                newname: data.fetch_str()?,
+                name: data.fetch_name()?,
             },
+            FuseOpCode::FUSE_RENAME => {
+                let arg = data.fetch_ref()?;
+                let (oldname, newname) = data.fetch_two_names()?;
+                Operation::Rename {
+                    arg,
+                    oldname,
+                    newname,
+                }
+            }
             FuseOpCode::FUSE_LINK => Operation::Link {
                 arg: data.fetch_ref()?,
-                name: data.fetch_str()?,
+                name: data.fetch_name()?,
             },
             FuseOpCode::FUSE_OPEN => Operation::Open {
                 arg: data.fetch_ref()?,
@@ -418,24 +400,29 @@ impl<'a> Operation<'a> {
             },
             FuseOpCode::FUSE_SETXATTR => Operation::SetXAttr {
                 arg: data.fetch_ref()?,
-                name: data.fetch_str()?,
+                name: data.fetch_name()?,
                 value: data.fetch_all_bytes(),
             },
             FuseOpCode::FUSE_GETXATTR => Operation::GetXAttr {
                 arg: data.fetch_ref()?,
-                name: data.fetch_str()?,
+                name: data.fetch_name()?,
             },
             FuseOpCode::FUSE_LISTXATTR => Operation::ListXAttr {
                 arg: data.fetch_ref()?,
             },
             FuseOpCode::FUSE_REMOVEXATTR => Operation::RemoveXAttr {
-                name: data.fetch_str()?,
+                name: data.fetch_name()?,
             },
             FuseOpCode::FUSE_FLUSH => Operation::Flush {
                 arg: data.fetch_ref()?,
             },
             FuseOpCode::FUSE_INIT => Operation::Init {
                 arg: data.fetch_ref()?,
+                ext: if data.remaining_len() >= mem::size_of::<FuseInitInExt>() {
+                    Some(data.fetch_ref()?)
+                } else {
+                    None
+                },
             },
             FuseOpCode::FUSE_OPENDIR => Operation::OpenDir {
                 arg: data.fetch_ref()?,
@@ -463,7 +450,7 @@ impl<'a> Operation<'a> {
             },
             FuseOpCode::FUSE_CREATE => Operation::Create {
                 arg: data.fetch_ref()?,
-                name: data.fetch_str()?,
+                name: data.fetch_name()?,
             },
             FuseOpCode::FUSE_INTERRUPT => Operation::Interrupt {
                 arg: data.fetch_ref()?,
@@ -499,11 +486,15 @@ impl<'a> Operation<'a> {
                 arg: data.fetch_ref()?,
             },
             #[cfg(feature = "abi-7-23")]
-            FuseOpCode::FUSE_RENAME2 => Operation::Rename2 {
-                arg: data.fetch_ref()?,
-                oldname: data.fetch_str()?,
-                newname: data.fetch_str()?,
-            },
+            FuseOpCode::FUSE_RENAME2 => {
+                let arg = data.fetch_ref()?;
+                let (oldname, newname) = data.fetch_two_names()?;
+                Operation::Rename2 {
+                    arg,
+                    oldname,
+                    newname,
+                }
+            }
             // #[cfg(feature = "abi-7-24")]
             FuseOpCode::FUSE_LSEEK => Operation::LSeek {
                 arg: data.fetch_ref()?,
@@ -518,6 +509,692 @@ impl<'a> Operation<'a> {
             },
         })
     }
+
+    /// Returns the `fuse_lk_in` argument of a `GETLK`, `SETLK` or `SETLKW`
+    /// operation, or `None` for any other operation.
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn lock_arg(&self) -> Option<&'a FuseLockIn> {
+        if let Operation::GetLk { arg } | Operation::SetLk { arg } | Operation::SetLkW { arg } =
+            *self
+        {
+            Some(arg)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the 64-bit capability set of an `INIT` operation, combining
+    /// `flags` with `flags2` as the upper 32 bits when the kernel set
+    /// `FUSE_INIT_EXT`, or `None` for any other operation.
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub fn init_capabilities(&self) -> Option<u64> {
+        if let Operation::Init { arg, ext } = *self {
+            // `flags2` is only valid if the kernel flagged the extension
+            let flags2 = if arg.flags & FUSE_INIT_EXT == 0 {
+                0
+            } else {
+                ext.map_or(0, |e| e.flags2)
+            };
+            Some((u64::from(flags2) << 32_i32) | u64::from(arg.flags))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the file handle of a `GETATTR` operation if the kernel set
+    /// `FUSE_GETATTR_FH`, i.e. the attributes of an open file are requested,
+    /// or `None` otherwise.
+    #[cfg(feature = "abi-7-9")]
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn getattr_fh(&self) -> Option<u64> {
+        if let Operation::GetAttr { arg: Some(arg) } = *self {
+            if arg.getattr_flags & FUSE_GETATTR_FH != 0 {
+                return Some(arg.fh);
+            }
+        }
+        None
+    }
+
+    /// Returns the file handle this operation targets, or `None` if it does
+    /// not carry one. For `COPY_FILE_RANGE` the source handle is returned,
+    /// and `GETATTR` and `SETATTR` only carry a handle if the kernel flagged it
+    /// as valid.
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn file_handle(&self) -> Option<u64> {
+        #[cfg(feature = "abi-7-9")]
+        if let Operation::GetAttr { .. } = *self {
+            return self.getattr_fh();
+        }
+        #[cfg(feature = "abi-7-11")]
+        if let Operation::IoCtl { arg, .. } = *self {
+            return Some(arg.fh);
+        }
+        #[cfg(feature = "abi-7-11")]
+        if let Operation::Poll { arg } = *self {
+            return Some(arg.fh);
+        }
+        #[cfg(feature = "abi-7-19")]
+        if let Operation::FAllocate { arg } = *self {
+            return Some(arg.fh);
+        }
+        #[cfg(feature = "abi-7-21")]
+        if let Operation::ReadDirPlus { arg } = *self {
+            return Some(arg.fh);
+        }
+        if let Operation::Read { arg } | Operation::ReadDir { arg } = *self {
+            Some(arg.fh)
+        } else if let Operation::Write { arg, .. } = *self {
+            Some(arg.fh)
+        } else if let Operation::Release { arg } | Operation::ReleaseDir { arg } = *self {
+            Some(arg.fh)
+        } else if let Operation::FSync { arg } | Operation::FSyncDir { arg } = *self {
+            Some(arg.fh)
+        } else if let Operation::Flush { arg } = *self {
+            Some(arg.fh)
+        } else if let Some(arg) = self.lock_arg() {
+            Some(arg.fh)
+        } else if let Operation::LSeek { arg } = *self {
+            Some(arg.fh)
+        } else if let Operation::CopyFileRange { arg } = *self {
+            Some(arg.fh_in)
+        } else if let Operation::SetAttr { arg } = *self {
+            if arg.valid & FATTR_FH == 0 {
+                None
+            } else {
+                Some(arg.fh)
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Returns the typed `valid` bitmask of a `SETATTR` operation, or `None`
+    /// for any other operation.
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn setattr_valid(&self) -> Option<SetAttrValid> {
+        if let Operation::SetAttr { arg } = *self {
+            Some(arg.valid_flags())
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of payload bytes carried by this operation, i.e. the
+    /// data of `WRITE`, `IOCTL` and `NOTIFY_REPLY` and the value of
+    /// `SETXATTR`, or 0 for operations without a payload. The raw arguments of
+    /// an unknown operation are not counted as payload.
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn data_len(&self) -> usize {
+        #[cfg(feature = "abi-7-11")]
+        if let Operation::IoCtl { data, .. } = *self {
+            return data.len();
+        }
+        #[cfg(feature = "abi-7-15")]
+        if let Operation::NotifyReply { data } = *self {
+            return data.len();
+        }
+        if let Operation::Write { data, .. } = *self {
+            data.len()
+        } else if let Operation::SetXAttr { value, .. } = *self {
+            value.len()
+        } else {
+            0
+        }
+    }
+
+    /// Returns the number of bytes this operation asks to move as declared in
+    /// its argument, e.g. `size` of `READ`, `READDIR` and `WRITE` or `len` of
+    /// `COPY_FILE_RANGE`, or 0 for operations without a declared size.
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub fn declared_len(&self) -> u64 {
+        #[cfg(feature = "abi-7-21")]
+        if let Operation::ReadDirPlus { arg } = *self {
+            return u64::from(arg.size);
+        }
+        if let Operation::Read { arg } | Operation::ReadDir { arg } = *self {
+            u64::from(arg.size)
+        } else if let Operation::Write { arg, .. } = *self {
+            u64::from(arg.size)
+        } else if let Operation::GetXAttr { arg, .. } | Operation::ListXAttr { arg } = *self {
+            u64::from(arg.size)
+        } else if let Operation::CopyFileRange { arg } = *self {
+            arg.len
+        } else {
+            0
+        }
+    }
+
+    /// Returns the category of this operation
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn category(&self) -> OpCategory {
+        match *self {
+            Operation::Read { .. }
+            | Operation::ReadDir { .. }
+            | Operation::ReadLink
+            | Operation::GetXAttr { .. }
+            | Operation::ListXAttr { .. } => OpCategory::Read,
+            #[cfg(feature = "abi-7-21")]
+            Operation::ReadDirPlus { .. } => OpCategory::Read,
+            Operation::Write { .. }
+            | Operation::SetAttr { .. }
+            | Operation::SymLink { .. }
+            | Operation::MkNod { .. }
+            | Operation::MkDir { .. }
+            | Operation::Unlink { .. }
+            | Operation::RmDir { .. }
+            | Operation::Rename { .. }
+            | Operation::Link { .. }
+            | Operation::FSync { .. }
+            | Operation::SetXAttr { .. }
+            | Operation::RemoveXAttr { .. }
+            | Operation::Flush { .. }
+            | Operation::FSyncDir { .. }
+            | Operation::Create { .. }
+            | Operation::CopyFileRange { .. } => OpCategory::Write,
+            #[cfg(feature = "abi-7-19")]
+            Operation::FAllocate { .. } => OpCategory::Write,
+            #[cfg(feature = "abi-7-23")]
+            Operation::Rename2 { .. } => OpCategory::Write,
+            Operation::Lookup { .. }
+            | Operation::Forget { .. }
+            | Operation::GetAttr { .. }
+            | Operation::Open { .. }
+            | Operation::StatFs
+            | Operation::Release { .. }
+            | Operation::OpenDir { .. }
+            | Operation::ReleaseDir { .. }
+            | Operation::Access { .. }
+            | Operation::BMap { .. }
+            | Operation::LSeek { .. } => OpCategory::Metadata,
+            #[cfg(feature = "abi-7-16")]
+            Operation::BatchForget { .. } => OpCategory::Metadata,
+            Operation::GetLk { .. } | Operation::SetLk { .. } | Operation::SetLkW { .. } => {
+                OpCategory::Lock
+            }
+            Operation::Init { .. } | Operation::Interrupt { .. } | Operation::Destroy => {
+                OpCategory::Control
+            }
+            #[cfg(feature = "abi-7-11")]
+            Operation::IoCtl { .. } | Operation::Poll { .. } | Operation::CuseInit { .. } => {
+                OpCategory::Control
+            }
+            #[cfg(feature = "abi-7-15")]
+            Operation::NotifyReply { .. } => OpCategory::Control,
+            Operation::Unknown { .. } => OpCategory::Control,
+        }
+    }
+
+    /// Returns the scheduling priority of this operation
+    #[inline]
+    #[must_use]
+    pub const fn priority(&self) -> RequestPriority {
+        match *self {
+            Operation::Interrupt { .. } | Operation::Forget { .. } | Operation::Flush { .. } => {
+                RequestPriority::High
+            }
+            #[cfg(feature = "abi-7-16")]
+            Operation::BatchForget { .. } => RequestPriority::High,
+            Operation::Read { .. } | Operation::ReadDir { .. } | Operation::Write { .. } => {
+                RequestPriority::Low
+            }
+            #[cfg(feature = "abi-7-21")]
+            Operation::ReadDirPlus { .. } => RequestPriority::Low,
+            Operation::CopyFileRange { .. } => RequestPriority::Low,
+            Operation::Lookup { .. }
+            | Operation::GetAttr { .. }
+            | Operation::SetAttr { .. }
+            | Operation::ReadLink
+            | Operation::SymLink { .. }
+            | Operation::MkNod { .. }
+            | Operation::MkDir { .. }
+            | Operation::Unlink { .. }
+            | Operation::RmDir { .. }
+            | Operation::Rename { .. }
+            | Operation::Link { .. }
+            | Operation::Open { .. }
+            | Operation::StatFs
+            | Operation::Release { .. }
+            | Operation::FSync { .. }
+            | Operation::SetXAttr { .. }
+            | Operation::GetXAttr { .. }
+            | Operation::ListXAttr { .. }
+            | Operation::RemoveXAttr { .. }
+            | Operation::Init { .. }
+            | Operation::OpenDir { .. }
+            | Operation::ReleaseDir { .. }
+            | Operation::FSyncDir { .. }
+            | Operation::GetLk { .. }
+            | Operation::SetLk { .. }
+            | Operation::SetLkW { .. }
+            | Operation::Access { .. }
+            | Operation::Create { .. }
+            | Operation::BMap { .. }
+            | Operation::Destroy
+            | Operation::LSeek { .. }
+            | Operation::Unknown { .. } => RequestPriority::Normal,
+            #[cfg(feature = "abi-7-11")]
+            Operation::IoCtl { .. } | Operation::Poll { .. } | Operation::CuseInit { .. } => {
+                RequestPriority::Normal
+            }
+            #[cfg(feature = "abi-7-15")]
+            Operation::NotifyReply { .. } => RequestPriority::Normal,
+            #[cfg(feature = "abi-7-19")]
+            Operation::FAllocate { .. } => RequestPriority::Normal,
+            #[cfg(feature = "abi-7-23")]
+            Operation::Rename2 { .. } => RequestPriority::Normal,
+        }
+    }
+
+    /// Dispatch this operation to the corresponding method of the visitor
+    #[allow(dead_code)]
+    #[inline]
+    pub fn accept(&self, visitor: &mut impl OperationVisitor) {
+        match *self {
+            Operation::Lookup { name } => visitor.visit_lookup(name),
+            Operation::Forget { arg } => visitor.visit_forget(arg),
+            #[cfg(feature = "abi-7-9")]
+            Operation::GetAttr { arg } => visitor.visit_getattr(arg),
+            #[cfg(not(feature = "abi-7-9"))]
+            Operation::GetAttr {} => visitor.visit_getattr(),
+            Operation::SetAttr { arg } => visitor.visit_setattr(arg),
+            Operation::ReadLink => visitor.visit_readlink(),
+            Operation::SymLink { name, link } => visitor.visit_symlink(name, link),
+            Operation::MkNod { arg, name } => visitor.visit_mknod(arg, name),
+            Operation::MkDir { arg, name } => visitor.visit_mkdir(arg, name),
+            Operation::Unlink { name } => visitor.visit_unlink(name),
+            Operation::RmDir { name } => visitor.visit_rmdir(name),
+            Operation::Rename {
+                arg,
+                oldname,
+                newname,
+            } => visitor.visit_rename(arg, oldname, newname),
+            Operation::Link { arg, name } => visitor.visit_link(arg, name),
+            Operation::Open { arg } => visitor.visit_open(arg),
+            Operation::Read { arg } => visitor.visit_read(arg),
+            Operation::Write { arg, data } => visitor.visit_write(arg, data),
+            Operation::StatFs => visitor.visit_statfs(),
+            Operation::Release { arg } => visitor.visit_release(arg),
+            Operation::FSync { arg } => visitor.visit_fsync(arg),
+            Operation::SetXAttr { arg, name, value } => visitor.visit_setxattr(arg, name, value),
+            Operation::GetXAttr { arg, name } => visitor.visit_getxattr(arg, name),
+            Operation::ListXAttr { arg } => visitor.visit_listxattr(arg),
+            Operation::RemoveXAttr { name } => visitor.visit_removexattr(name),
+            Operation::Flush { arg } => visitor.visit_flush(arg),
+            Operation::Init { arg, ext } => visitor.visit_init(arg, ext),
+            Operation::OpenDir { arg } => visitor.visit_opendir(arg),
+            Operation::ReadDir { arg } => visitor.visit_readdir(arg),
+            Operation::ReleaseDir { arg } => visitor.visit_releasedir(arg),
+            Operation::FSyncDir { arg } => visitor.visit_fsyncdir(arg),
+            Operation::GetLk { arg } => visitor.visit_getlk(arg),
+            Operation::SetLk { arg } => visitor.visit_setlk(arg),
+            Operation::SetLkW { arg } => visitor.visit_setlkw(arg),
+            Operation::Access { arg } => visitor.visit_access(arg),
+            Operation::Create { arg, name } => visitor.visit_create(arg, name),
+            Operation::Interrupt { arg } => visitor.visit_interrupt(arg),
+            Operation::BMap { arg } => visitor.visit_bmap(arg),
+            Operation::Destroy => visitor.visit_destroy(),
+            #[cfg(feature = "abi-7-11")]
+            Operation::IoCtl { arg, data } => visitor.visit_ioctl(arg, data),
+            #[cfg(feature = "abi-7-11")]
+            Operation::Poll { arg } => visitor.visit_poll(arg),
+            #[cfg(feature = "abi-7-15")]
+            Operation::NotifyReply { data } => visitor.visit_notify_reply(data),
+            #[cfg(feature = "abi-7-16")]
+            Operation::BatchForget { arg, nodes } => visitor.visit_batch_forget(arg, nodes),
+            #[cfg(feature = "abi-7-19")]
+            Operation::FAllocate { arg } => visitor.visit_fallocate(arg),
+            #[cfg(feature = "abi-7-21")]
+            Operation::ReadDirPlus { arg } => visitor.visit_readdirplus(arg),
+            #[cfg(feature = "abi-7-23")]
+            Operation::Rename2 {
+                arg,
+                oldname,
+                newname,
+            } => visitor.visit_rename2(arg, oldname, newname),
+            Operation::LSeek { arg } => visitor.visit_lseek(arg),
+            Operation::CopyFileRange { arg } => visitor.visit_copy_file_range(arg),
+            #[cfg(feature = "abi-7-11")]
+            Operation::CuseInit { arg } => visitor.visit_cuse_init(arg),
+            Operation::Unknown { opcode, data } => visitor.visit_unknown(opcode, data),
+        }
+    }
+}
+
+/// The coarse category of a FUSE operation, used to route requests
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCategory {
+    /// Reads file data, directory entries, links or extended attributes
+    Read,
+    /// Modifies file data, attributes or the namespace
+    Write,
+    /// Looks up or queries inodes and handles without modifying them
+    Metadata,
+    /// Queries or acquires POSIX locks
+    Lock,
+    /// Controls the session rather than the filesystem
+    Control,
+}
+
+/// The scheduling priority of a FUSE operation, higher priorities are served
+/// first under load.
+///
+/// - `High`: operations that unblock the kernel or user space quickly and are
+///   cheap to serve, i.e. `INTERRUPT`, `FORGET`, `BATCH_FORGET` and `FLUSH`,
+///   so that e.g. Ctrl-C is not stuck behind a backlog of bulk reads.
+/// - `Normal`: metadata, namespace, lock and session operations.
+/// - `Low`: bulk data transfers, i.e. `READ`, `WRITE`, `READDIR`,
+///   `READDIRPLUS` and `COPY_FILE_RANGE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RequestPriority {
+    /// Bulk data transfers
+    Low,
+    /// Most operations
+    Normal,
+    /// Latency-sensitive and cheap operations
+    High,
+}
+
+/// A visitor of FUSE operations.
+///
+/// Every method handles one kind of operation and does nothing by default, so
+/// an implementation only overrides the operations it cares about.
+#[allow(dead_code)]
+pub trait OperationVisitor {
+    /// Visit a `FUSE_LOOKUP` operation
+    #[inline]
+    fn visit_lookup(&mut self, _name: &OsStr) {}
+    /// Visit a `FUSE_FORGET` operation
+    #[inline]
+    fn visit_forget(&mut self, _arg: &FuseForgetIn) {}
+    /// Visit a `FUSE_GETATTR` operation
+    #[cfg(feature = "abi-7-9")]
+    #[inline]
+    fn visit_getattr(&mut self, _arg: Option<&FuseGetAttrIn>) {}
+    /// Visit a `FUSE_GETATTR` operation
+    #[cfg(not(feature = "abi-7-9"))]
+    #[inline]
+    fn visit_getattr(&mut self) {}
+    /// Visit a `FUSE_SETATTR` operation
+    #[inline]
+    fn visit_setattr(&mut self, _arg: &FuseSetAttrIn) {}
+    /// Visit a `FUSE_READLINK` operation
+    #[inline]
+    fn visit_readlink(&mut self) {}
+    /// Visit a `FUSE_SYMLINK` operation
+    #[inline]
+    fn visit_symlink(&mut self, _name: &OsStr, _link: &OsStr) {}
+    /// Visit a `FUSE_MKNOD` operation
+    #[inline]
+    fn visit_mknod(&mut self, _arg: &FuseMkNodIn, _name: &OsStr) {}
+    /// Visit a `FUSE_MKDIR` operation
+    #[inline]
+    fn visit_mkdir(&mut self, _arg: &FuseMkDirIn, _name: &OsStr) {}
+    /// Visit a `FUSE_UNLINK` operation
+    #[inline]
+    fn visit_unlink(&mut self, _name: &OsStr) {}
+    /// Visit a `FUSE_RMDIR` operation
+    #[inline]
+    fn visit_rmdir(&mut self, _name: &OsStr) {}
+    /// Visit a `FUSE_RENAME` operation
+    #[inline]
+    fn visit_rename(&mut self, _arg: &FuseRenameIn, _oldname: &OsStr, _newname: &OsStr) {}
+    /// Visit a `FUSE_LINK` operation
+    #[inline]
+    fn visit_link(&mut self, _arg: &FuseLinkIn, _name: &OsStr) {}
+    /// Visit a `FUSE_OPEN` operation
+    #[inline]
+    fn visit_open(&mut self, _arg: &FuseOpenIn) {}
+    /// Visit a `FUSE_READ` operation
+    #[inline]
+    fn visit_read(&mut self, _arg: &FuseReadIn) {}
+    /// Visit a `FUSE_WRITE` operation
+    #[inline]
+    fn visit_write(&mut self, _arg: &FuseWriteIn, _data: &[u8]) {}
+    /// Visit a `FUSE_STATFS` operation
+    #[inline]
+    fn visit_statfs(&mut self) {}
+    /// Visit a `FUSE_RELEASE` operation
+    #[inline]
+    fn visit_release(&mut self, _arg: &FuseReleaseIn) {}
+    /// Visit a `FUSE_FSYNC` operation
+    #[inline]
+    fn visit_fsync(&mut self, _arg: &FuseFSyncIn) {}
+    /// Visit a `FUSE_SETXATTR` operation
+    #[inline]
+    fn visit_setxattr(&mut self, _arg: &FuseSetXAttrIn, _name: &OsStr, _value: &[u8]) {}
+    /// Visit a `FUSE_GETXATTR` operation
+    #[inline]
+    fn visit_getxattr(&mut self, _arg: &FuseGetXAttrIn, _name: &OsStr) {}
+    /// Visit a `FUSE_LISTXATTR` operation
+    #[inline]
+    fn visit_listxattr(&mut self, _arg: &FuseGetXAttrIn) {}
+    /// Visit a `FUSE_REMOVEXATTR` operation
+    #[inline]
+    fn visit_removexattr(&mut self, _name: &OsStr) {}
+    /// Visit a `FUSE_FLUSH` operation
+    #[inline]
+    fn visit_flush(&mut self, _arg: &FuseFlushIn) {}
+    /// Visit a `FUSE_INIT` operation
+    #[inline]
+    fn visit_init(&mut self, _arg: &FuseInitIn, _ext: Option<&FuseInitInExt>) {}
+    /// Visit a `FUSE_OPENDIR` operation
+    #[inline]
+    fn visit_opendir(&mut self, _arg: &FuseOpenIn) {}
+    /// Visit a `FUSE_READDIR` operation
+    #[inline]
+    fn visit_readdir(&mut self, _arg: &FuseReadIn) {}
+    /// Visit a `FUSE_RELEASEDIR` operation
+    #[inline]
+    fn visit_releasedir(&mut self, _arg: &FuseReleaseIn) {}
+    /// Visit a `FUSE_FSYNCDIR` operation
+    #[inline]
+    fn visit_fsyncdir(&mut self, _arg: &FuseFSyncIn) {}
+    /// Visit a `FUSE_GETLK` operation
+    #[inline]
+    fn visit_getlk(&mut self, _arg: &FuseLockIn) {}
+    /// Visit a `FUSE_SETLK` operation
+    #[inline]
+    fn visit_setlk(&mut self, _arg: &FuseLockIn) {}
+    /// Visit a `FUSE_SETLKW` operation
+    #[inline]
+    fn visit_setlkw(&mut self, _arg: &FuseLockIn) {}
+    /// Visit a `FUSE_ACCESS` operation
+    #[inline]
+    fn visit_access(&mut self, _arg: &FuseAccessIn) {}
+    /// Visit a `FUSE_CREATE` operation
+    #[inline]
+    fn visit_create(&mut self, _arg: &FuseCreateIn, _name: &OsStr) {}
+    /// Visit a `FUSE_INTERRUPT` operation
+    #[inline]
+    fn visit_interrupt(&mut self, _arg: &FuseInterruptIn) {}
+    /// Visit a `FUSE_BMAP` operation
+    #[inline]
+    fn visit_bmap(&mut self, _arg: &FuseBMapIn) {}
+    /// Visit a `FUSE_DESTROY` operation
+    #[inline]
+    fn visit_destroy(&mut self) {}
+    /// Visit a `FUSE_IOCTL` operation
+    #[cfg(feature = "abi-7-11")]
+    #[inline]
+    fn visit_ioctl(&mut self, _arg: &FuseIoCtlIn, _data: &[u8]) {}
+    /// Visit a `FUSE_POLL` operation
+    #[cfg(feature = "abi-7-11")]
+    #[inline]
+    fn visit_poll(&mut self, _arg: &FusePollIn) {}
+    /// Visit a `FUSE_NOTIFY_REPLY` operation
+    #[cfg(feature = "abi-7-15")]
+    #[inline]
+    fn visit_notify_reply(&mut self, _data: &[u8]) {}
+    /// Visit a `FUSE_BATCH_FORGET` operation
+    #[cfg(feature = "abi-7-16")]
+    #[inline]
+    fn visit_batch_forget(&mut self, _arg: &FuseBatchForgetIn, _nodes: &[FuseForgetOne]) {}
+    /// Visit a `FUSE_FALLOCATE` operation
+    #[cfg(feature = "abi-7-19")]
+    #[inline]
+    fn visit_fallocate(&mut self, _arg: &FuseFAllocateIn) {}
+    /// Visit a `FUSE_READDIRPLUS` operation
+    #[cfg(feature = "abi-7-21")]
+    #[inline]
+    fn visit_readdirplus(&mut self, _arg: &FuseReadIn) {}
+    /// Visit a `FUSE_RENAME2` operation
+    #[cfg(feature = "abi-7-23")]
+    #[inline]
+    fn visit_rename2(&mut self, _arg: &FuseRename2In, _oldname: &OsStr, _newname: &OsStr) {}
+    /// Visit a `FUSE_LSEEK` operation
+    #[inline]
+    fn visit_lseek(&mut self, _arg: &FuseLSeekIn) {}
+    /// Visit a `FUSE_COPY_FILE_RANGE` operation
+    #[inline]
+    fn visit_copy_file_range(&mut self, _arg: &FuseCopyFileRangeIn) {}
+    /// Visit a `CUSE_INIT` operation
+    #[cfg(feature = "abi-7-11")]
+    #[inline]
+    fn visit_cuse_init(&mut self, _arg: &FuseInitIn) {}
+    /// Visit an operation with an unknown opcode
+    #[inline]
+    fn visit_unknown(&mut self, _opcode: u32, _data: &[u8]) {}
+}
+
+/// Decodes a FUSE opcode, failing on opcodes unknown to the enabled ABI
+/// version.
+const fn decode_opcode(n: u32) -> Result<FuseOpCode, DeserializeError> {
+    Ok(match n {
+        1 => FuseOpCode::FUSE_LOOKUP,
+        2 => FuseOpCode::FUSE_FORGET,
+        3 => FuseOpCode::FUSE_GETATTR,
+        4 => FuseOpCode::FUSE_SETATTR,
+        5 => FuseOpCode::FUSE_READLINK,
+        6 => FuseOpCode::FUSE_SYMLINK,
+        8 => FuseOpCode::FUSE_MKNOD,
+        9 => FuseOpCode::FUSE_MKDIR,
+        10 => FuseOpCode::FUSE_UNLINK,
+        11 => FuseOpCode::FUSE_RMDIR,
+        12 => FuseOpCode::FUSE_RENAME,
+        13 => FuseOpCode::FUSE_LINK,
+        14 => FuseOpCode::FUSE_OPEN,
+        15 => FuseOpCode::FUSE_READ,
+        16 => FuseOpCode::FUSE_WRITE,
+        17 => FuseOpCode::FUSE_STATFS,
+        18 => FuseOpCode::FUSE_RELEASE,
+        20 => FuseOpCode::FUSE_FSYNC,
+        21 => FuseOpCode::FUSE_SETXATTR,
+        22 => FuseOpCode::FUSE_GETXATTR,
+        23 => FuseOpCode::FUSE_LISTXATTR,
+        24 => FuseOpCode::FUSE_REMOVEXATTR,
+        25 => FuseOpCode::FUSE_FLUSH,
+        26 => FuseOpCode::FUSE_INIT,
+        27 => FuseOpCode::FUSE_OPENDIR,
+        28 => FuseOpCode::FUSE_READDIR,
+        29 => FuseOpCode::FUSE_RELEASEDIR,
+        30 => FuseOpCode::FUSE_FSYNCDIR,
+        31 => FuseOpCode::FUSE_GETLK,
+        32 => FuseOpCode::FUSE_SETLK,
+        33 => FuseOpCode::FUSE_SETLKW,
+        34 => FuseOpCode::FUSE_ACCESS,
+        35 => FuseOpCode::FUSE_CREATE,
+        36 => FuseOpCode::FUSE_INTERRUPT,
+        37 => FuseOpCode::FUSE_BMAP,
+        38 => FuseOpCode::FUSE_DESTROY,
+        #[cfg(feature = "abi-7-11")]
+        39 => FuseOpCode::FUSE_IOCTL,
+        #[cfg(feature = "abi-7-11")]
+        40 => FuseOpCode::FUSE_POLL,
+        #[cfg(feature = "abi-7-15")]
+        41 => FuseOpCode::FUSE_NOTIFY_REPLY,
+        #[cfg(feature = "abi-7-16")]
+        42 => FuseOpCode::FUSE_BATCH_FORGET,
+        #[cfg(feature = "abi-7-19")]
+        43 => FuseOpCode::FUSE_FALLOCATE,
+        #[cfg(feature = "abi-7-21")]
+        44 => FuseOpCode::FUSE_READDIRPLUS,
+        #[cfg(feature = "abi-7-23")]
+        45 => FuseOpCode::FUSE_RENAME2,
+        // #[cfg(feature = "abi-7-24")]
+        46 => FuseOpCode::FUSE_LSEEK,
+        // #[cfg(feature = "abi-7-28")]
+        47 => FuseOpCode::FUSE_COPY_FILE_RANGE,
+        #[cfg(feature = "abi-7-11")]
+        4096 => FuseOpCode::CUSE_INIT,
+
+        code => return Err(DeserializeError::UnknownOpCode { code, unique: None }),
+    })
+}
+
+/// Returns the size of the fixed argument struct of a FUSE opcode, which is
+/// the minimum length of the request body after `fuse_in_header`.
+///
+/// Trailing names and data are not counted, so opcodes without a fixed
+/// argument have a minimum length of zero.
+const fn min_arg_len(opcode: &FuseOpCode) -> usize {
+    match *opcode {
+        FuseOpCode::FUSE_LOOKUP
+        | FuseOpCode::FUSE_GETATTR
+        | FuseOpCode::FUSE_READLINK
+        | FuseOpCode::FUSE_SYMLINK
+        | FuseOpCode::FUSE_UNLINK
+        | FuseOpCode::FUSE_RMDIR
+        | FuseOpCode::FUSE_STATFS
+        | FuseOpCode::FUSE_REMOVEXATTR
+        | FuseOpCode::FUSE_DESTROY => 0,
+        #[cfg(feature = "abi-7-15")]
+        FuseOpCode::FUSE_NOTIFY_REPLY => 0,
+        FuseOpCode::FUSE_FORGET => mem::size_of::<FuseForgetIn>(),
+        FuseOpCode::FUSE_SETATTR => mem::size_of::<FuseSetAttrIn>(),
+        FuseOpCode::FUSE_MKNOD => mem::size_of::<FuseMkNodIn>(),
+        FuseOpCode::FUSE_MKDIR => mem::size_of::<FuseMkDirIn>(),
+        FuseOpCode::FUSE_RENAME => mem::size_of::<FuseRenameIn>(),
+        FuseOpCode::FUSE_LINK => mem::size_of::<FuseLinkIn>(),
+        FuseOpCode::FUSE_OPEN | FuseOpCode::FUSE_OPENDIR => mem::size_of::<FuseOpenIn>(),
+        FuseOpCode::FUSE_READ | FuseOpCode::FUSE_READDIR => mem::size_of::<FuseReadIn>(),
+        #[cfg(feature = "abi-7-21")]
+        FuseOpCode::FUSE_READDIRPLUS => mem::size_of::<FuseReadIn>(),
+        FuseOpCode::FUSE_WRITE => mem::size_of::<FuseWriteIn>(),
+        FuseOpCode::FUSE_RELEASE | FuseOpCode::FUSE_RELEASEDIR => mem::size_of::<FuseReleaseIn>(),
+        FuseOpCode::FUSE_FSYNC | FuseOpCode::FUSE_FSYNCDIR => mem::size_of::<FuseFSyncIn>(),
+        FuseOpCode::FUSE_SETXATTR => mem::size_of::<FuseSetXAttrIn>(),
+        FuseOpCode::FUSE_GETXATTR | FuseOpCode::FUSE_LISTXATTR => mem::size_of::<FuseGetXAttrIn>(),
+        FuseOpCode::FUSE_FLUSH => mem::size_of::<FuseFlushIn>(),
+        FuseOpCode::FUSE_INIT => mem::size_of::<FuseInitIn>(),
+        #[cfg(feature = "abi-7-11")]
+        FuseOpCode::CUSE_INIT => mem::size_of::<FuseInitIn>(),
+        FuseOpCode::FUSE_GETLK | FuseOpCode::FUSE_SETLK | FuseOpCode::FUSE_SETLKW => {
+            mem::size_of::<FuseLockIn>()
+        }
+        FuseOpCode::FUSE_ACCESS => mem::size_of::<FuseAccessIn>(),
+        FuseOpCode::FUSE_CREATE => mem::size_of::<FuseCreateIn>(),
+        FuseOpCode::FUSE_INTERRUPT => mem::size_of::<FuseInterruptIn>(),
+        FuseOpCode::FUSE_BMAP => mem::size_of::<FuseBMapIn>(),
+        #[cfg(feature = "abi-7-11")]
+        FuseOpCode::FUSE_IOCTL => mem::size_of::<FuseIoCtlIn>(),
+        #[cfg(feature = "abi-7-11")]
+        FuseOpCode::FUSE_POLL => mem::size_of::<FusePollIn>(),
+        #[cfg(feature = "abi-7-16")]
+        FuseOpCode::FUSE_BATCH_FORGET => mem::size_of::<FuseBatchForgetIn>(),
+        #[cfg(feature = "abi-7-19")]
+        FuseOpCode::FUSE_FALLOCATE => mem::size_of::<FuseFAllocateIn>(),
+        #[cfg(feature = "abi-7-23")]
+        FuseOpCode::FUSE_RENAME2 => mem::size_of::<FuseRename2In>(),
+        FuseOpCode::FUSE_LSEEK => mem::size_of::<FuseLSeekIn>(),
+        FuseOpCode::FUSE_COPY_FILE_RANGE => mem::size_of::<FuseCopyFileRangeIn>(),
+    }
 }
 
 impl fmt::Display for Operation<'_> {
@@ -527,7 +1204,7 @@ impl fmt::Display for Operation<'_> {
         match *self {
             Operation::Lookup { name } => write!(f, "LOOKUP name={name:?}"),
             Operation::Forget { arg } => write!(f, "FORGET nlookup={}", arg.nlookup),
-            Operation::GetAttr => write!(f, "GETATTR"),
+            Operation::GetAttr { .. } => write!(f, "GETATTR"),
             Operation::SetAttr { arg } => write!(f, "SETATTR valid={:#x}", arg.valid),
             Operation::ReadLink => write!(f, "READLINK"),
             Operation::SymLink { name, link } => {
@@ -588,7 +1265,7 @@ impl fmt::Display for Operation<'_> {
             Operation::Flush { arg } => {
                 write!(f, "FLUSH fh={}, lock owner={}", arg.fh, arg.lock_owner)
             }
-            Operation::Init { arg } => write!(
+            Operation::Init { arg, .. } => write!(
                 f,
                 "INIT kernel ABI={}.{}, flags={:#x}, max readahead={}",
                 arg.major, arg.minor, arg.flags, arg.max_readahead
@@ -686,10 +1363,33 @@ impl fmt::Display for Operation<'_> {
                 "CUSE INIT kernel ABI={}.{}, flags={:#x}, max readahead={}",
                 arg.major, arg.minor, arg.flags, arg.max_readahead,
             ),
+            Operation::Unknown { opcode, data } => {
+                write!(f, "UNKNOWN opcode={opcode}, data.len={}", data.len())
+            }
         }
     }
 }
 
+/// The header fields of a FUSE request, parsed without its arguments
+#[allow(dead_code)] // Not every field is read by the session yet
+#[derive(Debug)]
+pub struct HeaderView {
+    /// Request size
+    pub len: u32,
+    /// FUSE operation code
+    pub opcode: FuseOpCode,
+    /// The request unique ID
+    pub unique: u64,
+    /// The i-number of the node
+    pub nodeid: u64,
+    /// User ID
+    pub uid: u32,
+    /// Group ID
+    pub gid: u32,
+    /// Process ID
+    pub pid: u32,
+}
+
 /// FUSE request
 #[derive(Debug)]
 pub struct Request<'a> {
@@ -711,9 +1411,30 @@ impl fmt::Display for Request<'_> {
 
 impl<'a> Request<'a> {
     /// Build FUSE request
+    ///
+    /// Names are accepted as any bytes, see [`Request::new_utf8`] to require
+    /// valid UTF-8 names.
     pub fn new(bytes: &'a [u8], proto_version: ProtoVersion) -> Result<Self, DeserializeError> {
-        let data_len = bytes.len();
-        let mut de = Deserializer::new(bytes);
+        Self::parse(Deserializer::new(bytes), proto_version)
+    }
+
+    /// Build FUSE request like [`Request::new`], but reject the requests with
+    /// a name not valid UTF-8 by `NonUtf8Name`, e.g. for a filesystem storing
+    /// names as `str`.
+    #[inline]
+    pub fn new_utf8(
+        bytes: &'a [u8],
+        proto_version: ProtoVersion,
+    ) -> Result<Self, DeserializeError> {
+        Self::parse(Deserializer::new(bytes).utf8_names(), proto_version)
+    }
+
+    /// Parse FUSE request with the deserializer of the whole request bytes
+    fn parse(
+        mut de: Deserializer<'a>,
+        proto_version: ProtoVersion,
+    ) -> Result<Self, DeserializeError> {
+        let data_len = de.remaining_len();
         // Parse header
         let header = de.fetch_ref::<FuseInHeader>()?;
         // Check data size
@@ -738,7 +1459,7 @@ impl<'a> Request<'a> {
             debug!(
                 "request bytes is not completely consumed: \
                     bytes.len() = {}, header = {:?}, de.remaining_len() = {}, de = {:?}",
-                bytes.len(),
+                data_len,
                 header,
                 de.remaining_len(),
                 de
@@ -748,6 +1469,60 @@ impl<'a> Request<'a> {
         Ok(Self { header, operation })
     }
 
+    /// Build FUSE request like [`Request::new`], but an unknown opcode yields
+    /// [`Operation::Unknown`] instead of an error, so that the caller can reply
+    /// `ENOSYS` to requests from newer kernels and keep running.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn new_tolerant(
+        bytes: &'a [u8],
+        proto_version: ProtoVersion,
+    ) -> Result<Self, DeserializeError> {
+        match Self::new(bytes, proto_version) {
+            Err(DeserializeError::UnknownOpCode { code, .. }) => {
+                let mut de = Deserializer::new(bytes);
+                let header = de.fetch_ref::<FuseInHeader>()?;
+                Ok(Self {
+                    header,
+                    operation: Operation::Unknown {
+                        opcode: code,
+                        data: de.fetch_all_bytes(),
+                    },
+                })
+            }
+            result => result,
+        }
+    }
+
+    /// Parse only the header of a FUSE request, e.g. to triage requests before
+    /// parsing them.
+    ///
+    /// The opcode is validated like [`Request::new`] does, but the arguments
+    /// are neither parsed nor checked.
+    #[inline]
+    pub fn peek_header(bytes: &[u8]) -> Result<HeaderView, DeserializeError> {
+        let header = Deserializer::new(bytes).fetch_ref::<FuseInHeader>()?;
+        let opcode = decode_opcode(header.opcode).map_err(|e| {
+            if let DeserializeError::UnknownOpCode { code, .. } = e {
+                DeserializeError::UnknownOpCode {
+                    code,
+                    unique: Some(header.unique),
+                }
+            } else {
+                e
+            }
+        })?;
+        Ok(HeaderView {
+            len: header.len,
+            opcode,
+            unique: header.unique,
+            nodeid: header.nodeid,
+            uid: header.uid,
+            gid: header.gid,
+            pid: header.pid,
+        })
+    }
+
     /// Returns the unique identifier of this request.
     ///
     /// The FUSE kernel driver assigns a unique id to every concurrent request.
@@ -767,6 +1542,14 @@ impl<'a> Request<'a> {
         self.header.nodeid
     }
 
+    /// Returns whether this request is targeted to the root inode.
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn is_for_root(&self) -> bool {
+        self.header.nodeid == FUSE_ROOT_ID
+    }
+
     /// Returns the UID that the process that triggered this request runs under.
     #[allow(dead_code)]
     #[inline]
@@ -815,6 +1598,147 @@ impl<'a> Request<'a> {
     }
 }
 
+/// A FUSE request backed by a refcounted buffer, e.g. a frozen `BytesMut`
+/// from a buffer pool.
+///
+/// It owns a reference to the buffer instead of borrowing it, so it can be
+/// stored or sent to another task without copying the request bytes. The
+/// buffer is validated on construction, and [`OwnedRequest::request`] parses
+/// the whole request again on every call, which does not copy the bytes
+/// either, so the parsed request should be kept while it is used.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct OwnedRequest {
+    /// The request bytes
+    bytes: Bytes,
+    /// The protocol version the request is parsed with
+    proto_version: ProtoVersion,
+}
+
+impl OwnedRequest {
+    /// Build FUSE request from a refcounted buffer
+    #[allow(dead_code)]
+    #[inline]
+    pub fn new(
+        bytes: impl Into<Bytes>,
+        proto_version: ProtoVersion,
+    ) -> Result<Self, DeserializeError> {
+        let bytes = bytes.into();
+        Request::new(&bytes, proto_version)?;
+        Ok(Self {
+            bytes,
+            proto_version,
+        })
+    }
+
+    /// Returns the request borrowing from the owned buffer.
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub fn request(&self) -> Request<'_> {
+        Request::new(&self.bytes, self.proto_version).unwrap_or_else(|e| {
+            unreachable!("the request is validated on construction, the error is: {e}")
+        })
+    }
+
+    /// Returns the underlying buffer.
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn bytes(&self) -> &Bytes {
+        &self.bytes
+    }
+}
+
+/// Copy the bytes of a request to a buffer aligned for `fuse_in_header`, as
+/// requests are parsed in place.
+#[allow(dead_code)]
+fn aligned_copy(bytes: &[u8]) -> Bytes {
+    let align = mem::align_of::<FuseInHeader>();
+    // The spare capacity makes room for the padding, so `buf` is never
+    // reallocated, and the offset is kept
+    let mut buf = Vec::<u8>::with_capacity(bytes.len().saturating_add(align));
+    let offset = buf.as_ptr().align_offset(align);
+    buf.resize(offset, 0);
+    buf.extend_from_slice(bytes);
+    Bytes::from(buf).slice(offset..)
+}
+
+/// Assembles FUSE requests from bytes read in several chunks.
+///
+/// A single read from the FUSE device is not guaranteed to return a whole
+/// request on all platforms. The assembler buffers the bytes read so far, and
+/// uses the `len` field of `fuse_in_header` to split complete requests off,
+/// keeping the leftover bytes for the following reads.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct RequestAssembler {
+    /// The bytes not assembled into a request yet
+    buf: BytesMut,
+    /// The protocol version the requests are parsed with
+    proto_version: ProtoVersion,
+}
+
+impl RequestAssembler {
+    /// Create an empty assembler
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub fn new(proto_version: ProtoVersion) -> Self {
+        Self {
+            buf: BytesMut::new(),
+            proto_version,
+        }
+    }
+
+    /// Append the bytes of a read to the buffer
+    #[allow(dead_code)]
+    #[inline]
+    pub fn extend(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Returns the number of bytes buffered but not assembled yet.
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub fn buffered_len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Split the next request off the buffer.
+    ///
+    /// Returns `None` if no complete request is buffered yet. A request
+    /// failing to parse is still consumed, so that the following requests can
+    /// be assembled. If the `len` of the header is shorter than the header,
+    /// no request boundary can be found after it, so an error is returned and
+    /// the buffered bytes are kept as is.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn next_request(&mut self) -> Option<Result<OwnedRequest, DeserializeError>> {
+        // `len` is the first field of `fuse_in_header`, read it by bytes as
+        // the buffer may not be aligned
+        let mut len_bytes = [0_u8; mem::size_of::<u32>()];
+        len_bytes.copy_from_slice(self.buf.get(..mem::size_of::<u32>())?);
+        #[allow(clippy::host_endian_bytes)] // FUSE requests are in host byte order
+        let len: usize = u32::from_ne_bytes(len_bytes).cast();
+        if len < mem::size_of::<FuseInHeader>() {
+            return Some(Err(DeserializeError::InvalidValue));
+        }
+        if self.buf.len() < len {
+            return None;
+        }
+
+        let bytes = self.buf.split_to(len).freeze();
+        let bytes = if bytes.as_ptr().align_offset(mem::align_of::<FuseInHeader>()) == 0 {
+            bytes
+        } else {
+            aligned_copy(&bytes)
+        };
+        Some(OwnedRequest::new(bytes, self.proto_version))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use tracing::debug;
@@ -916,6 +1840,101 @@ mod test {
         debug!("short read request={:?}", req);
     }
 
+    define_payload! {
+        NON_UTF8_LOOKUP_REQUEST;
+        len: 48;
+        opcode: 1;
+        str: b"f\xffo.txt\0",  // name
+    }
+
+    #[test]
+    fn non_utf8_name() {
+        use std::os::unix::ffi::OsStrExt;
+
+        // Any name is accepted by default.
+        let req = Request::new(&NON_UTF8_LOOKUP_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *req.operation() {
+            Operation::Lookup { name } => assert_eq!(name, OsStr::from_bytes(b"f\xffo.txt")),
+            _ => panic!("unexpected request operation"),
+        }
+
+        // Only UTF-8 names are accepted in strict mode.
+        let req = Request::new_utf8(&LOOKUP_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *req.operation() {
+            Operation::Lookup { name } => assert_eq!(name, "foo.txt"),
+            _ => panic!("unexpected request operation"),
+        }
+        #[allow(clippy::expect_used)]
+        let err = Request::new_utf8(&NON_UTF8_LOOKUP_REQUEST[..], PROTO_VERSION)
+            .expect_err("Unexpected request parsing result");
+        assert_eq!(err, DeserializeError::NonUtf8Name);
+        // The header is still available to reply an error.
+        let header = Request::peek_header(&NON_UTF8_LOOKUP_REQUEST[..])
+            .unwrap_or_else(|err| panic!("failed to peek FUSE header, the error is: {err}"));
+        assert_eq!(header.unique, 0xdead_beef_baad_f00d);
+    }
+
+    define_payload! {
+        UNKNOWN_REQUEST;
+        len: 48;
+        opcode: 0xdead;
+        u64: 0x10,  // unknown argument
+    }
+
+    #[test]
+    fn unknown_opcode() {
+        #[allow(clippy::expect_used)]
+        let err = Request::new(&UNKNOWN_REQUEST[..], PROTO_VERSION)
+            .expect_err("Unexpected request parsing result");
+        assert_eq!(
+            err,
+            DeserializeError::UnknownOpCode {
+                code: 0xdead,
+                unique: Some(0xdead_beef_baad_f00d),
+            }
+        );
+
+        let req = Request::new_tolerant(&UNKNOWN_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        assert_eq!(UNKNOWN_REQUEST.len(), req.len().cast::<usize>());
+        check_header(&req);
+
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *req.operation() {
+            Operation::Unknown { opcode, data } => {
+                assert_eq!(opcode, 0xdead);
+                assert_eq!(data.len(), 8);
+            }
+            _ => panic!("unexpected request operation"),
+        }
+    }
+
+    define_payload! {
+        TRUNCATED_READ_REQUEST;
+        len: 48;
+        opcode: 15;
+        u64: 0x10,  // fh
+    }
+
+    #[test]
+    fn short_read_arg() {
+        #[allow(clippy::expect_used)]
+        let err = Request::new(&TRUNCATED_READ_REQUEST[..], PROTO_VERSION)
+            .expect_err("Unexpected request parsing result");
+        assert_eq!(
+            err,
+            DeserializeError::ShortRead {
+                code: 15,
+                expected: mem::size_of::<FuseReadIn>(),
+                actual: 8,
+            }
+        );
+    }
+
     fn check_header(req: &Request<'_>) {
         assert_eq!(req.unique(), 0xdead_beef_baad_f00d);
         assert_eq!(req.nodeid(), 0x1122_3344_5566_7788);
@@ -986,9 +2005,50 @@ mod test {
 
         #[allow(clippy::wildcard_enum_match_arm)]
         match *req.operation() {
-            Operation::GetAttr => {}
+            Operation::GetAttr {
+                #[cfg(feature = "abi-7-9")]
+                arg,
+            } => {
+                #[cfg(feature = "abi-7-9")]
+                assert!(arg.is_none());
+            }
+            _ => panic!("unexpected request operation"),
+        }
+        #[cfg(feature = "abi-7-9")]
+        assert_eq!(req.operation().getattr_fh(), None);
+    }
+
+    #[cfg(feature = "abi-7-9")]
+    define_payload! {
+        GETATTR_FH_REQUEST;
+        len: 56;
+        opcode: 3;
+        u32: 1,     // getattr_flags
+        u32: 0,     // dummy
+        u64: 0x10,  // fh
+    }
+
+    #[test]
+    #[cfg(feature = "abi-7-9")]
+    fn getattr_fh() {
+        use super::super::protocol::FUSE_GETATTR_FH;
+
+        let req = Request::new(&GETATTR_FH_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        assert_eq!(GETATTR_FH_REQUEST.len(), req.len().cast::<usize>());
+        assert_eq!(req.header.opcode, 3);
+        check_header(&req);
+
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *req.operation() {
+            Operation::GetAttr { arg } => {
+                let arg = arg.unwrap_or_else(|| panic!("getattr argument is missing"));
+                assert_eq!(arg.getattr_flags, FUSE_GETATTR_FH);
+                assert_eq!(arg.fh, 0x10);
+            }
             _ => panic!("unexpected request operation"),
         }
+        assert_eq!(req.operation().getattr_fh(), Some(0x10));
     }
 
     define_payload! {
@@ -1047,6 +2107,50 @@ mod test {
         }
     }
 
+    #[test]
+    fn setattr_valid() {
+        use super::super::protocol::{FATTR_GID, FATTR_MODE};
+
+        let req = Request::new(&SETATTR_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        let valid = req
+            .operation()
+            .setattr_valid()
+            .unwrap_or_else(|| panic!("SETATTR should carry a valid bitmask"));
+        assert_eq!(valid.bits(), FATTR_MODE | FATTR_GID);
+        assert_eq!(valid, SetAttrValid::MODE | SetAttrValid::GID);
+        assert!(valid.contains(SetAttrValid::MODE));
+        assert!(valid.contains(SetAttrValid::GID));
+        assert!(!valid.contains(SetAttrValid::UID));
+        assert!(!valid.contains(SetAttrValid::SIZE));
+        assert!(!valid.contains(SetAttrValid::MODE | SetAttrValid::SIZE));
+        #[cfg(feature = "abi-7-9")]
+        assert!(!valid.contains(SetAttrValid::LOCKOWNER));
+        #[cfg(feature = "abi-7-23")]
+        assert!(!valid.contains(SetAttrValid::CTIME));
+
+        assert_eq!(valid & SetAttrValid::MODE, SetAttrValid::MODE);
+        assert!((valid & SetAttrValid::SIZE).is_empty());
+        assert_eq!(valid - SetAttrValid::MODE, SetAttrValid::GID);
+        assert_eq!(
+            valid.iter().collect::<Vec<_>>(),
+            vec![SetAttrValid::MODE, SetAttrValid::GID]
+        );
+        assert_eq!(format!("{valid:?}"), "SetAttrValid(MODE | GID)");
+        assert_eq!(
+            format!("{:?}", SetAttrValid::from_bits(FATTR_MODE | 0x8000_0000)),
+            "SetAttrValid(MODE | 0x80000000)"
+        );
+        assert_eq!(
+            format!("{:?}", SetAttrValid::from_bits(0)),
+            "SetAttrValid(0x0)"
+        );
+
+        let req = Request::new(&READLINK_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        assert_eq!(req.operation().setattr_valid(), None);
+    }
+
     define_payload! {
         READLINK_REQUEST;
         len: 40;
@@ -1248,6 +2352,23 @@ mod test {
         }
     }
 
+    define_payload! {
+        TRUNCATED_RENAME_REQUEST;
+        len: 59;
+        opcode: 12;
+        u64: 1,             // newdir
+        str: b"foo.txt\0",  // oldname
+        str: b"bar",        // truncated newname
+    }
+
+    #[test]
+    fn rename_truncated_newname() {
+        #[allow(clippy::expect_used)]
+        let err = Request::new(&TRUNCATED_RENAME_REQUEST[..], PROTO_VERSION)
+            .expect_err("Unexpected request parsing result");
+        assert_eq!(err, DeserializeError::MissingSecondName);
+    }
+
     define_payload! {
         LINK_REQUEST;
         len: 56;
@@ -1413,6 +2534,113 @@ mod test {
         }
     }
 
+    #[cfg(not(feature = "abi-7-9"))]
+    define_payload! {
+        ZERO_READ_REQUEST;
+        len: 64;
+        opcode: 15;
+        u64: 0x10,  // fh
+        u64: 0x0a,  // offset
+        u32: 0,     // size
+        u32: 0,     // padding
+    }
+
+    #[cfg(feature = "abi-7-9")]
+    define_payload! {
+        ZERO_READ_REQUEST;
+        len: 80;
+        opcode: 15;
+        u64: 0x10,  // fh
+        u64: 0x0a,  // offset
+        u32: 0,     // size
+        u32: 0,     // read_flags
+        u64: 0,     // lock_owner
+        u32: 2,     // flags
+        u32: 0,     // padding
+    }
+
+    #[test]
+    fn zero_length_read() {
+        let req = Request::new(&ZERO_READ_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        assert_eq!(ZERO_READ_REQUEST.len(), req.len().cast::<usize>());
+        check_header(&req);
+
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *req.operation() {
+            Operation::Read { arg } => {
+                assert_eq!(arg.offset, 0x0a);
+                assert_eq!(arg.size, 0);
+            }
+            _ => panic!("unexpected request operation"),
+        }
+    }
+
+    #[cfg(not(feature = "abi-7-9"))]
+    define_payload! {
+        ZERO_WRITE_REQUEST;
+        len: 64;
+        opcode: 16;
+        u64: 0x10,  // fh
+        u64: 0x0a,  // offset
+        u32: 0,     // size
+        u32: 0,     // write_flags
+    }
+
+    #[cfg(feature = "abi-7-9")]
+    define_payload! {
+        ZERO_WRITE_REQUEST;
+        len: 80;
+        opcode: 16;
+        u64: 0x10,  // fh
+        u64: 0x0a,  // offset
+        u32: 0,     // size
+        u32: 0,     // write_flags
+        u64: 0,     // lock_owner
+        u32: 2,     // flags
+        u32: 0,     // padding
+    }
+
+    #[test]
+    fn zero_length_write() {
+        let req = Request::new(&ZERO_WRITE_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        assert_eq!(ZERO_WRITE_REQUEST.len(), req.len().cast::<usize>());
+        check_header(&req);
+
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *req.operation() {
+            Operation::Write { arg, data } => {
+                assert_eq!(arg.offset, 0x0a);
+                assert_eq!(arg.size, 0);
+                assert!(data.is_empty());
+            }
+            _ => panic!("unexpected request operation"),
+        }
+    }
+
+    #[test]
+    fn write_flags() {
+        for (payload, lock_owner, is_writeback) in [
+            #[cfg(feature = "abi-7-9")]
+            (&WRITE_REQUEST[..], Some(0x1234), true),
+            #[cfg(not(feature = "abi-7-9"))]
+            (&WRITE_REQUEST[..], None, false),
+            (&ZERO_WRITE_REQUEST[..], None, false),
+        ] {
+            let req = Request::new(payload, PROTO_VERSION)
+                .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+            #[allow(clippy::wildcard_enum_match_arm)]
+            match *req.operation() {
+                Operation::Write { arg, .. } => {
+                    assert_eq!(arg.lock_owner(), lock_owner);
+                    assert_eq!(arg.is_writeback(), is_writeback);
+                }
+                _ => panic!("unexpected request operation"),
+            }
+        }
+    }
+
     define_payload! {
         STATFS_REQUEST;
         len: 40;
@@ -1434,6 +2662,30 @@ mod test {
         }
     }
 
+    define_data! {
+        ROOT_STATFS_REQUEST;
+        u32: 40,                     // len
+        u32: 17,                     // opcode
+        u64: 0xdead_beef_baad_f00d,  // unique
+        u64: 1,                      // nodeid
+        u32: 0xc001_d00d,            // uid
+        u32: 0xc001_cafe,            // gid
+        u32: 0xc0de_ba5e,            // pid
+        u32: 0,                      // padding
+    }
+
+    #[test]
+    fn is_for_root() {
+        let req = Request::new(&ROOT_STATFS_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        assert_eq!(req.nodeid(), FUSE_ROOT_ID);
+        assert!(req.is_for_root());
+
+        let req = Request::new(&STATFS_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        assert!(!req.is_for_root());
+    }
+
     define_payload! {
         RELEASE_REQUEST;
         len: 64;
@@ -1461,11 +2713,41 @@ mod test {
                 assert_eq!(arg.flags, FOPEN_KEEP_CACHE);
                 assert_eq!(arg.release_flags, 0);
                 assert_eq!(arg.lock_owner, 0x1234);
+                assert!(!arg.should_flush());
+                assert!(!arg.should_unlock_flock());
+                assert_eq!(arg.lock_owner(), None);
             }
             _ => panic!("unexpected request operation"),
         }
     }
 
+    #[test]
+    fn release_flags() {
+        #[cfg(feature = "abi-7-17")]
+        use super::super::protocol::FUSE_RELEASE_FLOCK_UNLOCK;
+        use super::super::protocol::FUSE_RELEASE_FLUSH;
+
+        for (release_flags, flush, unlock) in [
+            (0, false, false),
+            (FUSE_RELEASE_FLUSH, true, false),
+            #[cfg(feature = "abi-7-17")]
+            (FUSE_RELEASE_FLOCK_UNLOCK, false, true),
+            #[cfg(feature = "abi-7-17")]
+            (FUSE_RELEASE_FLUSH | FUSE_RELEASE_FLOCK_UNLOCK, true, true),
+        ] {
+            let arg = FuseReleaseIn {
+                fh: 0x10,
+                flags: 0,
+                release_flags,
+                lock_owner: 0x1234,
+            };
+            assert_eq!(arg.should_flush(), flush);
+            assert_eq!(arg.should_unlock_flock(), unlock);
+            let lock_owner = (flush || unlock).then_some(0x1234);
+            assert_eq!(arg.lock_owner(), lock_owner);
+        }
+    }
+
     define_payload! {
         FSYNC_REQUEST;
         len: 56;
@@ -1649,7 +2931,8 @@ mod test {
 
         #[allow(clippy::wildcard_enum_match_arm)]
         match *req.operation() {
-            Operation::Init { arg } => {
+            Operation::Init { arg, ext } => {
+                assert!(ext.is_none());
                 assert_eq!(arg.major, 7);
                 assert_eq!(arg.minor, 8);
                 assert_eq!(arg.max_readahead, 4096);
@@ -1659,6 +2942,93 @@ mod test {
         }
     }
 
+    define_payload! {
+        INIT_EXT_REQUEST;
+        len: 104;
+        opcode: 26;
+        u32: 7,                      // major
+        u32: 36,                     // minor
+        u32: 0x1000,                 // max_readahead
+        u32: 0x4000_0001,            // flags, FUSE_INIT_EXT | FUSE_ASYNC_READ
+        u32: 0x3,                    // flags2
+        u32: 0,                      // unused[0]
+        u32: 0,                      // unused[1]
+        u32: 0,                      // unused[2]
+        u32: 0,                      // unused[3]
+        u32: 0,                      // unused[4]
+        u32: 0,                      // unused[5]
+        u32: 0,                      // unused[6]
+        u32: 0,                      // unused[7]
+        u32: 0,                      // unused[8]
+        u32: 0,                      // unused[9]
+        u32: 0,                      // unused[10]
+    }
+
+    define_payload! {
+        INIT_EXT_UNFLAGGED_REQUEST;
+        len: 104;
+        opcode: 26;
+        u32: 7,                      // major
+        u32: 36,                     // minor
+        u32: 0x1000,                 // max_readahead
+        u32: 0x1,                    // flags, FUSE_ASYNC_READ
+        u32: 0x3,                    // flags2, not flagged as valid
+        u32: 0,                      // unused[0]
+        u32: 0,                      // unused[1]
+        u32: 0,                      // unused[2]
+        u32: 0,                      // unused[3]
+        u32: 0,                      // unused[4]
+        u32: 0,                      // unused[5]
+        u32: 0,                      // unused[6]
+        u32: 0,                      // unused[7]
+        u32: 0,                      // unused[8]
+        u32: 0,                      // unused[9]
+        u32: 0,                      // unused[10]
+    }
+
+    #[test]
+    fn init_capabilities() {
+        use super::super::protocol::FUSE_ASYNC_READ;
+
+        let req = Request::new(&INIT_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        assert_eq!(
+            req.operation().init_capabilities(),
+            Some(u64::from(FUSE_ASYNC_READ))
+        );
+
+        let req = Request::new(&INIT_EXT_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        assert_eq!(INIT_EXT_REQUEST.len(), req.header.len.cast::<usize>());
+        check_header(&req);
+
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *req.operation() {
+            Operation::Init { arg, ext } => {
+                assert_eq!(arg.minor, 36);
+                assert_eq!(arg.flags, FUSE_INIT_EXT | FUSE_ASYNC_READ);
+                let ext = ext.unwrap_or_else(|| panic!("flags2 should be parsed"));
+                assert_eq!(ext.flags2, 0x3);
+            }
+            _ => panic!("unexpected request operation"),
+        }
+        assert_eq!(
+            req.operation().init_capabilities(),
+            Some((0x3_u64 << 32_i32) | u64::from(FUSE_INIT_EXT | FUSE_ASYNC_READ))
+        );
+
+        let req = Request::new(&INIT_EXT_UNFLAGGED_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        assert_eq!(
+            req.operation().init_capabilities(),
+            Some(u64::from(FUSE_ASYNC_READ))
+        );
+
+        let req = Request::new(&READLINK_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        assert_eq!(req.operation().init_capabilities(), None);
+    }
+
     define_payload! {
         OPENDIR_REQUEST;
         len: 48;
@@ -1966,6 +3336,54 @@ mod test {
         }
     }
 
+    #[cfg(not(feature = "abi-7-9"))]
+    define_payload! {
+        SETLK_RANGE_REQUEST;
+        len: 80;
+        opcode: 32;
+        u64: 0x10,      // fh
+        u64: 0x11,      // owner
+        u64: 0x1000,    // lk.start
+        u64: 0x1fff,    // lk.end
+        u32: 1,         // lk.typ, F_WRLCK
+        u32: 0xff,      // lk.pid
+    }
+
+    #[cfg(feature = "abi-7-9")]
+    define_payload! {
+        SETLK_RANGE_REQUEST;
+        len: 88;
+        opcode: 32;
+        u64: 0x10,      // fh
+        u64: 0x11,      // owner
+        u64: 0x1000,    // lk.start
+        u64: 0x1fff,    // lk.end
+        u32: 1,         // lk.typ, F_WRLCK
+        u32: 0xff,      // lk.pid
+        u32: 0,         // lk_flags
+        u32: 0,         // padding
+    }
+
+    #[test]
+    fn lock_range() {
+        let req = Request::new(&SETLK_RANGE_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        check_header(&req);
+
+        let arg = req
+            .operation()
+            .lock_arg()
+            .unwrap_or_else(|| panic!("SETLK should carry a lock argument"));
+        assert_eq!(arg.fh, 0x10);
+        assert_eq!(arg.owner, 0x11);
+        assert_eq!(arg.lk.pid, 0xff);
+        assert_eq!(arg.lock_range(), (0x1000, 0x1fff, 1));
+
+        let req = Request::new(&ACCESS_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        assert!(req.operation().lock_arg().is_none());
+    }
+
     define_payload! {
         ACCESS_REQUEST;
         len: 48;
@@ -1991,6 +3409,42 @@ mod test {
         }
     }
 
+    define_payload! {
+        ACCESS_RX_REQUEST;
+        len: 48;
+        opcode: 34;
+        u32: 0o0005,  // mask, R_OK | X_OK
+        u32: 0,       // padding
+    }
+
+    #[test]
+    fn access_mask() {
+        use super::super::protocol::{F_OK, R_OK, X_OK};
+
+        let req = Request::new(&ACCESS_RX_REQUEST[..], PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *req.operation() {
+            Operation::Access { arg } => {
+                assert_eq!(arg.mask, R_OK | X_OK);
+                assert!(arg.wants_read());
+                assert!(!arg.wants_write());
+                assert!(arg.wants_exec());
+                assert!(!arg.exists_only());
+            }
+            _ => panic!("unexpected request operation"),
+        }
+
+        let arg = FuseAccessIn {
+            mask: F_OK,
+            padding: 0,
+        };
+        assert!(arg.exists_only());
+        assert!(!arg.wants_read());
+        assert!(!arg.wants_write());
+        assert!(!arg.wants_exec());
+    }
+
     #[cfg(not(feature = "abi-7-12"))]
     define_payload! {
         CREATE_REQUEST;
@@ -2450,4 +3904,258 @@ mod test {
             _ => panic!("unexpected request operation"),
         }
     }
+
+    #[test]
+    fn visitor() {
+        /// Counts the operations it visits and the bytes written
+        #[derive(Default)]
+        struct CountingVisitor {
+            /// The number of `LOOKUP` operations
+            lookups: usize,
+            /// The number of `READ` operations
+            reads: usize,
+            /// The number of `WRITE` operations
+            writes: usize,
+            /// The total size of the `WRITE` payloads
+            written: usize,
+        }
+
+        impl OperationVisitor for CountingVisitor {
+            fn visit_lookup(&mut self, name: &OsStr) {
+                assert_eq!(name, "foo.txt");
+                self.lookups += 1;
+            }
+
+            fn visit_read(&mut self, _arg: &FuseReadIn) {
+                self.reads += 1;
+            }
+
+            fn visit_write(&mut self, _arg: &FuseWriteIn, data: &[u8]) {
+                self.writes += 1;
+                self.written += data.len();
+            }
+        }
+
+        let mut visitor = CountingVisitor::default();
+        for payload in [
+            &LOOKUP_REQUEST[..],
+            &GETATTR_REQUEST[..],
+            &READ_REQUEST[..],
+            &WRITE_REQUEST[..],
+            &LOOKUP_REQUEST[..],
+            &STATFS_REQUEST[..],
+            &WRITE_REQUEST[..],
+        ] {
+            let req = Request::new(payload, PROTO_VERSION)
+                .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+            req.operation().accept(&mut visitor);
+        }
+
+        assert_eq!(visitor.lookups, 2);
+        assert_eq!(visitor.reads, 1);
+        assert_eq!(visitor.writes, 2);
+        assert_eq!(visitor.written, 16);
+    }
+
+    #[test]
+    fn data_len() {
+        for (payload, data_len, declared_len) in [
+            (&WRITE_REQUEST[..], 8, 0x10),
+            (&READ_REQUEST[..], 0, 0x10),
+            (&LOOKUP_REQUEST[..], 0, 0),
+            (&UNKNOWN_REQUEST[..], 0, 0),
+        ] {
+            let req = Request::new_tolerant(payload, PROTO_VERSION)
+                .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+            assert_eq!(req.operation().data_len(), data_len);
+            assert_eq!(req.operation().declared_len(), declared_len);
+        }
+    }
+
+    #[test]
+    fn file_handle() {
+        for (payload, fh) in [
+            (&READ_REQUEST[..], Some(0x10)),
+            (&WRITE_REQUEST[..], Some(0x10)),
+            (&RELEASE_REQUEST[..], Some(0x10)),
+            (&SETLK_REQUEST[..], Some(0x10)),
+            (&LOOKUP_REQUEST[..], None),
+            (&STATFS_REQUEST[..], None),
+        ] {
+            let req = Request::new(payload, PROTO_VERSION)
+                .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+            assert_eq!(req.operation().file_handle(), fh);
+        }
+    }
+
+    #[test]
+    fn category() {
+        for (payload, category) in [
+            (&READ_REQUEST[..], OpCategory::Read),
+            (&WRITE_REQUEST[..], OpCategory::Write),
+            (&LOOKUP_REQUEST[..], OpCategory::Metadata),
+            (&SETLK_REQUEST[..], OpCategory::Lock),
+            (&INIT_REQUEST[..], OpCategory::Control),
+        ] {
+            let req = Request::new(payload, PROTO_VERSION)
+                .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+            assert_eq!(req.operation().category(), category);
+        }
+    }
+
+    #[test]
+    fn priority() {
+        for (payload, priority) in [
+            (&INTERRUPT_REQUEST[..], RequestPriority::High),
+            (&FORGET_REQUEST[..], RequestPriority::High),
+            (&FLUSH_REQUEST[..], RequestPriority::High),
+            (&LOOKUP_REQUEST[..], RequestPriority::Normal),
+            (&SETLK_REQUEST[..], RequestPriority::Normal),
+            (&READ_REQUEST[..], RequestPriority::Low),
+            (&WRITE_REQUEST[..], RequestPriority::Low),
+            (&READDIR_REQUEST[..], RequestPriority::Low),
+        ] {
+            let req = Request::new(payload, PROTO_VERSION)
+                .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+            assert_eq!(req.operation().priority(), priority);
+        }
+        assert!(RequestPriority::High > RequestPriority::Normal);
+        assert!(RequestPriority::Normal > RequestPriority::Low);
+    }
+
+    #[test]
+    fn owned_request() {
+        let mut pool = BytesMut::with_capacity(4096);
+        pool.extend_from_slice(&READ_REQUEST[..]);
+        let owned = OwnedRequest::new(pool.split(), PROTO_VERSION)
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        // The pool handle is gone, and the request keeps the buffer alive.
+        drop(pool);
+
+        let cloned = owned.clone();
+        drop(owned);
+        let req = cloned.request();
+        assert_eq!(READ_REQUEST.len(), req.len().cast::<usize>());
+        check_header(&req);
+        assert_eq!(req.operation().file_handle(), Some(0x10));
+        assert_eq!(cloned.bytes().len(), READ_REQUEST.len());
+
+        #[allow(clippy::expect_used)]
+        let err = OwnedRequest::new(Bytes::from_static(&[0_u8; 8]), PROTO_VERSION)
+            .expect_err("Unexpected request parsing result");
+        assert_eq!(err, DeserializeError::NotEnough);
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn peek_header() {
+        // The arguments are truncated, but the header is intact.
+        let err = Request::new(&TRUNCATED_READ_REQUEST[..], PROTO_VERSION)
+            .expect_err("Unexpected request parsing result");
+        assert!(
+            matches!(err, DeserializeError::ShortRead { .. }),
+            "err = {err:?}"
+        );
+        let header = Request::peek_header(&TRUNCATED_READ_REQUEST[..])
+            .unwrap_or_else(|err| panic!("failed to peek FUSE header, the error is: {err}"));
+        assert_eq!(header.len, 48);
+        assert!(matches!(header.opcode, FuseOpCode::FUSE_READ));
+        assert_eq!(header.unique, 0xdead_beef_baad_f00d);
+        assert_eq!(header.nodeid, 0x1122_3344_5566_7788);
+        assert_eq!(header.uid, 0xc001_d00d);
+        assert_eq!(header.gid, 0xc001_cafe);
+        assert_eq!(header.pid, 0xc0de_ba5e);
+
+        let err = Request::peek_header(&UNKNOWN_REQUEST[..])
+            .expect_err("Unexpected header parsing result");
+        assert_eq!(
+            err,
+            DeserializeError::UnknownOpCode {
+                code: 0xdead,
+                unique: Some(0xdead_beef_baad_f00d)
+            }
+        );
+        let err = Request::peek_header(
+            TRUNCATED_READ_REQUEST
+                .get(..32)
+                .unwrap_or_else(|| unreachable!()),
+        )
+        .expect_err("Unexpected header parsing result");
+        assert_eq!(err, DeserializeError::NotEnough);
+    }
+
+    #[test]
+    fn request_assembler() {
+        let mut assembler = RequestAssembler::new(PROTO_VERSION);
+        // Not even the header is read yet.
+        assembler.extend(READ_REQUEST.get(..2).unwrap_or_else(|| unreachable!()));
+        assert!(assembler.next_request().is_none());
+        assembler.extend(READ_REQUEST.get(2..60).unwrap_or_else(|| unreachable!()));
+        assert!(assembler.next_request().is_none());
+        assert_eq!(assembler.buffered_len(), 60);
+
+        // The rest of the request arrives with the head of another one.
+        assembler.extend(READ_REQUEST.get(60..).unwrap_or_else(|| unreachable!()));
+        assembler.extend(LOOKUP_REQUEST.get(..7).unwrap_or_else(|| unreachable!()));
+        let owned = assembler
+            .next_request()
+            .unwrap_or_else(|| panic!("a complete request is buffered"))
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        check_header(&owned.request());
+        assert_eq!(owned.request().operation().file_handle(), Some(0x10));
+        assert!(assembler.next_request().is_none());
+        assert_eq!(assembler.buffered_len(), 7);
+
+        assembler.extend(LOOKUP_REQUEST.get(7..).unwrap_or_else(|| unreachable!()));
+        let owned = assembler
+            .next_request()
+            .unwrap_or_else(|| panic!("a complete request is buffered"))
+            .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+        assert_eq!(LOOKUP_REQUEST.len(), owned.request().len().cast::<usize>());
+        assert!(assembler.next_request().is_none());
+        assert_eq!(assembler.buffered_len(), 0);
+    }
+
+    define_payload! {
+        SHORT_LOOKUP_REQUEST;
+        len: 44;
+        opcode: 1;
+        str: b"foo\0",  // name
+    }
+
+    #[test]
+    fn request_assembler_unaligned() {
+        let mut assembler = RequestAssembler::new(PROTO_VERSION);
+        // The second request starts at an offset not aligned for the header.
+        assembler.extend(&SHORT_LOOKUP_REQUEST[..]);
+        assembler.extend(&READ_REQUEST[..]);
+        for len in [SHORT_LOOKUP_REQUEST.len(), READ_REQUEST.len()] {
+            let owned = assembler
+                .next_request()
+                .unwrap_or_else(|| panic!("a complete request is buffered"))
+                .unwrap_or_else(|err| panic!("failed to build FUSE request, the error is: {err}"));
+            assert_eq!(owned.request().len().cast::<usize>(), len);
+            check_header(&owned.request());
+        }
+        assert!(assembler.next_request().is_none());
+    }
+
+    define_payload! {
+        BOGUS_LEN_REQUEST;
+        len: 8;
+        opcode: 1;
+        str: b"foo.txt\0",  // name
+    }
+
+    #[test]
+    fn request_assembler_bogus_len() {
+        let mut assembler = RequestAssembler::new(PROTO_VERSION);
+        assembler.extend(&BOGUS_LEN_REQUEST[..]);
+        assert_eq!(
+            assembler.next_request().map(|res| res.map(|_| ())),
+            Some(Err(DeserializeError::InvalidValue))
+        );
+        // The bytes are not discarded.
+        assert_eq!(assembler.buffered_len(), BOGUS_LEN_REQUEST.len());
+    }
 }
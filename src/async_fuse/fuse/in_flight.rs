@@ -0,0 +1,218 @@
+//! The registry of FUSE requests under processing
+
+use std::collections::{HashSet, VecDeque};
+
+use parking_lot::Mutex;
+use tracing::{debug, warn};
+
+/// The max number of interrupts kept for requests not read yet. An interrupt
+/// may also target a request already replied, such interrupts are never
+/// consumed, so the oldest ones are dropped beyond this limit.
+const MAX_PENDING_INTERRUPTS: usize = 64;
+
+/// Why a request is not registered in [`InFlightRequests`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterError {
+    /// A request with the same `unique` is already in flight
+    Duplicate,
+    /// The request was interrupted before it arrived
+    Interrupted,
+}
+
+/// The state of [`InFlightRequests`]
+#[derive(Debug, Default)]
+struct InFlightState {
+    /// The `unique` ids of the requests under processing
+    uniques: HashSet<u64>,
+    /// The `unique` ids of the interrupted requests not read yet, oldest first
+    pending_interrupts: VecDeque<u64>,
+}
+
+/// The registry of in-flight FUSE requests, keyed by the `unique` id in
+/// `fuse_in_header`.
+///
+/// The kernel never reuses a `unique` among concurrent requests, but a buggy
+/// kernel or a replaying proxy may do so. A duplicate is rejected instead of
+/// overwriting the tracking entry of the request already in flight, so that
+/// an interrupt never reaches the wrong request.
+///
+/// Since requests are read by several threads, a `FUSE_INTERRUPT` may be
+/// processed before the request it targets. Such an interrupt is kept pending,
+/// and the request is rejected as interrupted once it arrives.
+#[derive(Debug, Default)]
+pub struct InFlightRequests {
+    /// The registry state
+    state: Mutex<InFlightState>,
+}
+
+impl InFlightRequests {
+    /// Create an empty registry
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a request as in flight until the returned guard is dropped.
+    ///
+    /// Fails if a request with the same `unique` is already in flight, in
+    /// which case the registry is left untouched, or if the request has been
+    /// interrupted before it arrived.
+    #[inline]
+    pub fn register(&self, unique: u64) -> Result<InFlightGuard<'_>, RegisterError> {
+        let mut state = self.state.lock();
+        if state.uniques.contains(&unique) {
+            warn!("FUSE request unique={unique} is already in flight, reject the duplicate");
+            return Err(RegisterError::Duplicate);
+        }
+        if let Some(pos) = state.pending_interrupts.iter().position(|&u| u == unique) {
+            state.pending_interrupts.remove(pos);
+            debug!("FUSE request unique={unique} was interrupted before it arrived");
+            return Err(RegisterError::Interrupted);
+        }
+        state.uniques.insert(unique);
+        Ok(InFlightGuard {
+            registry: self,
+            unique,
+        })
+    }
+
+    /// Interrupt the request with `unique`.
+    ///
+    /// Returns whether the request is in flight. Otherwise the interrupt is
+    /// kept pending for the request to arrive.
+    #[inline]
+    pub fn interrupt(&self, unique: u64) -> bool {
+        let mut state = self.state.lock();
+        if state.uniques.contains(&unique) {
+            return true;
+        }
+        if !state.pending_interrupts.contains(&unique) {
+            if state.pending_interrupts.len() >= MAX_PENDING_INTERRUPTS {
+                state.pending_interrupts.pop_front();
+            }
+            state.pending_interrupts.push_back(unique);
+        }
+        false
+    }
+
+    /// Whether a request with `unique` is in flight
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, unique: u64) -> bool {
+        self.state.lock().uniques.contains(&unique)
+    }
+
+    /// The number of requests in flight
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.state.lock().uniques.len()
+    }
+
+    /// Whether there is no request in flight
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().uniques.is_empty()
+    }
+}
+
+/// Keeps a request registered in [`InFlightRequests`], and unregisters it on
+/// drop.
+#[derive(Debug)]
+pub struct InFlightGuard<'a> {
+    /// The registry holding the request
+    registry: &'a InFlightRequests,
+    /// The `unique` id of the request
+    unique: u64,
+}
+
+impl InFlightGuard<'_> {
+    /// The `unique` id of the request
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn unique(&self) -> u64 {
+        self.unique
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.registry.state.lock().uniques.remove(&self.unique);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod test {
+    use super::{InFlightRequests, RegisterError, MAX_PENDING_INTERRUPTS};
+
+    #[test]
+    fn duplicate_unique() {
+        let registry = InFlightRequests::new();
+        let first = registry.register(2).expect("the first request is accepted");
+        let other = registry.register(4).expect("another request is accepted");
+
+        // The duplicate is rejected, and dropping the rejection does not
+        // unregister the request in flight.
+        assert_eq!(
+            registry.register(2).expect_err("the duplicate is rejected"),
+            RegisterError::Duplicate
+        );
+        assert!(registry.contains(2));
+        assert_eq!(registry.len(), 2);
+
+        drop(first);
+        assert!(!registry.contains(2));
+        assert!(registry.contains(other.unique()));
+
+        // The `unique` may be used again once the request is done.
+        let again = registry.register(2).expect("the unique is free again");
+        drop(again);
+        drop(other);
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn interrupt_before_request() {
+        let registry = InFlightRequests::new();
+        let in_flight = registry.register(2).expect("the request is accepted");
+        assert!(registry.interrupt(in_flight.unique()));
+
+        // The interrupt arrives first, the request is cancelled once it arrives.
+        assert!(!registry.interrupt(4));
+        assert_eq!(
+            registry
+                .register(4)
+                .expect_err("the request is interrupted"),
+            RegisterError::Interrupted
+        );
+        assert!(!registry.contains(4));
+        // The pending interrupt is consumed.
+        drop(registry.register(4).expect("the unique is free again"));
+
+        // Interrupts of requests never arriving are bounded.
+        let max: u64 = MAX_PENDING_INTERRUPTS.try_into().expect("a small limit");
+        for unique in 10..=10 + max {
+            assert!(!registry.interrupt(unique));
+        }
+        drop(
+            registry
+                .register(10)
+                .expect("the oldest interrupt is dropped"),
+        );
+        assert_eq!(
+            registry
+                .register(11)
+                .expect_err("the request is interrupted"),
+            RegisterError::Interrupted
+        );
+        drop(in_flight);
+        assert!(registry.is_empty());
+    }
+}
@@ -12,8 +12,10 @@ pub mod file_system;
 pub mod channel;
 pub mod fuse_reply;
 pub mod fuse_request;
+pub mod in_flight;
 pub mod mount;
 // ioctl_read!() macro involves inter arithmetic
 #[allow(clippy::arithmetic_side_effects)]
 pub mod protocol;
+pub mod request_queue;
 pub mod session;
@@ -223,6 +223,164 @@ use std::mem;
 use clippy_utilities::{Cast, OverflowArithmetic};
 pub use setattr_flags::*;
 
+/// Typed view of the `fuse_setattr_in.valid` bitmask
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SetAttrValid(u32);
+
+#[allow(dead_code)]
+impl SetAttrValid {
+    /// To set file mode
+    pub const MODE: Self = Self(FATTR_MODE);
+    /// To set file user ID
+    pub const UID: Self = Self(FATTR_UID);
+    /// To set file group ID
+    pub const GID: Self = Self(FATTR_GID);
+    /// To set file size
+    pub const SIZE: Self = Self(FATTR_SIZE);
+    /// To set file access time
+    pub const ATIME: Self = Self(FATTR_ATIME);
+    /// To set content modified time
+    pub const MTIME: Self = Self(FATTR_MTIME);
+    /// To set file handler
+    pub const FH: Self = Self(FATTR_FH);
+    /// To set atime as of now
+    #[cfg(feature = "abi-7-9")]
+    pub const ATIME_NOW: Self = Self(FATTR_ATIME_NOW);
+    /// To set mtime as of now
+    #[cfg(feature = "abi-7-9")]
+    pub const MTIME_NOW: Self = Self(FATTR_MTIME_NOW);
+    /// To set file lock owner
+    #[cfg(feature = "abi-7-9")]
+    pub const LOCKOWNER: Self = Self(FATTR_LOCKOWNER);
+    /// To set meta-data change time
+    #[cfg(feature = "abi-7-23")]
+    pub const CTIME: Self = Self(FATTR_CTIME);
+
+    /// The known flags with their names, in bit order
+    const NAMED: &'static [(Self, &'static str)] = &[
+        (Self::MODE, "MODE"),
+        (Self::UID, "UID"),
+        (Self::GID, "GID"),
+        (Self::SIZE, "SIZE"),
+        (Self::ATIME, "ATIME"),
+        (Self::MTIME, "MTIME"),
+        (Self::FH, "FH"),
+        #[cfg(feature = "abi-7-9")]
+        (Self::ATIME_NOW, "ATIME_NOW"),
+        #[cfg(feature = "abi-7-9")]
+        (Self::MTIME_NOW, "MTIME_NOW"),
+        #[cfg(feature = "abi-7-9")]
+        (Self::LOCKOWNER, "LOCKOWNER"),
+        #[cfg(feature = "abi-7-23")]
+        (Self::CTIME, "CTIME"),
+    ];
+
+    /// Wrap the raw `valid` bits, unknown bits are kept as is
+    #[inline]
+    #[must_use]
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the raw `valid` bits
+    #[inline]
+    #[must_use]
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns if no bit is set
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns if all the bits of `other` are set
+    #[inline]
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the known flags that are set, unknown bits are skipped
+    #[inline]
+    pub fn iter(self) -> impl Iterator<Item = Self> {
+        Self::NAMED
+            .iter()
+            .map(|&(flag, _)| flag)
+            .filter(move |&flag| self.contains(flag))
+    }
+}
+
+impl std::ops::BitOr for SetAttrValid {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitAnd for SetAttrValid {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl std::ops::Sub for SetAttrValid {
+    type Output = Self;
+
+    /// Returns the bits of `self` that are not set in `rhs`
+    #[inline]
+    #[allow(clippy::suspicious_arithmetic_impl)] // Set difference, as bitflags does
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 & !rhs.0)
+    }
+}
+
+impl std::fmt::Debug for SetAttrValid {
+    /// Lists the names of the set flags, e.g. `SetAttrValid(MODE | GID)`,
+    /// with any unknown bits appended in hex
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SetAttrValid(")?;
+        let mut unknown = self.0;
+        let mut first = true;
+        for &(flag, name) in Self::NAMED {
+            if self.contains(flag) {
+                if !first {
+                    write!(f, " | ")?;
+                }
+                write!(f, "{name}")?;
+                unknown &= !flag.0;
+                first = false;
+            }
+        }
+        if unknown != 0 || first {
+            if !first {
+                write!(f, " | ")?;
+            }
+            write!(f, "{unknown:#x}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl FuseSetAttrIn {
+    /// Returns the typed `valid` bitmask
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn valid_flags(&self) -> SetAttrValid {
+        SetAttrValid::from_bits(self.valid)
+    }
+}
+
 /// Flags returned by the OPEN request
 ///
 /// `FOPEN_DIRECT_IO`: bypass page cache for this open file
@@ -341,6 +499,9 @@ pub mod init_flags {
     /// request
     #[cfg(feature = "abi-7-30")]
     pub const FUSE_EXPLICIT_INVAL_DATA: u32 = 1 << 25_i32;
+    /// `FUSE_INIT_EXT`: extended `fuse_init_in` request, `flags2` is valid
+    // #[cfg(feature = "abi-7-36")]
+    pub const FUSE_INIT_EXT: u32 = 1 << 30_i32;
 }
 
 pub use init_flags::*;
@@ -859,6 +1020,45 @@ pub struct FuseReleaseIn {
     pub lock_owner: u64,
 }
 
+impl FuseReleaseIn {
+    /// Returns whether the file should be flushed on this release, i.e.
+    /// `FUSE_RELEASE_FLUSH` is set
+    #[inline]
+    #[must_use]
+    pub const fn should_flush(&self) -> bool {
+        self.release_flags & FUSE_RELEASE_FLUSH != 0
+    }
+
+    /// Returns whether the `flock` locks held by `lock_owner` should be
+    /// dropped on this release, i.e. `FUSE_RELEASE_FLOCK_UNLOCK` is set
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn should_unlock_flock(&self) -> bool {
+        #[cfg(feature = "abi-7-17")]
+        {
+            self.release_flags & FUSE_RELEASE_FLOCK_UNLOCK != 0
+        }
+        #[cfg(not(feature = "abi-7-17"))]
+        {
+            false
+        }
+    }
+
+    /// Returns the lock owner if the kernel set it, i.e. this release flushes
+    /// or unlocks `flock` locks, or `None` otherwise
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn lock_owner(&self) -> Option<u64> {
+        if self.should_flush() || self.should_unlock_flock() {
+            Some(self.lock_owner)
+        } else {
+            None
+        }
+    }
+}
+
 /// FUSE flush request input `fuse_flush_in`
 #[derive(Debug)]
 #[repr(C)]
@@ -919,6 +1119,37 @@ pub struct FuseWriteIn {
     pub padding: u32,
 }
 
+impl FuseWriteIn {
+    /// Returns the lock owner if `FUSE_WRITE_LOCKOWNER` is set, or `None` if
+    /// the `lock_owner` field is not valid.
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn lock_owner(&self) -> Option<u64> {
+        #[cfg(feature = "abi-7-9")]
+        if self.write_flags & FUSE_WRITE_LOCKOWNER != 0 {
+            return Some(self.lock_owner);
+        }
+        None
+    }
+
+    /// Returns whether this is a delayed write from the page cache, i.e.
+    /// `FUSE_WRITE_CACHE` is set and the file handle is guessed.
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn is_writeback(&self) -> bool {
+        #[cfg(feature = "abi-7-9")]
+        {
+            self.write_flags & FUSE_WRITE_CACHE != 0
+        }
+        #[cfg(not(feature = "abi-7-9"))]
+        {
+            false
+        }
+    }
+}
+
 /// FUSE write response `fuse_write_out`
 #[derive(Debug)]
 #[repr(C)]
@@ -997,6 +1228,16 @@ pub struct FuseLockIn {
     pub padding: u32,
 }
 
+impl FuseLockIn {
+    /// Returns the byte range and type of the lock as `(start, end, type)`.
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn lock_range(&self) -> (u64, u64, u32) {
+        (self.lk.start, self.lk.end, self.lk.typ)
+    }
+}
+
 /// FUSE lock response `fuse_lk_out`
 #[derive(Debug)]
 #[repr(C)]
@@ -1015,6 +1256,49 @@ pub struct FuseAccessIn {
     pub padding: u32,
 }
 
+/// Test for existence of file, `F_OK` in `<unistd.h>`
+pub const F_OK: u32 = 0;
+/// Test for execute or search permission, `X_OK` in `<unistd.h>`
+pub const X_OK: u32 = 1 << 0;
+/// Test for write permission, `W_OK` in `<unistd.h>`
+pub const W_OK: u32 = 1 << 1;
+/// Test for read permission, `R_OK` in `<unistd.h>`
+pub const R_OK: u32 = 1 << 2;
+
+impl FuseAccessIn {
+    /// Returns whether read permission is requested (`R_OK`).
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn wants_read(&self) -> bool {
+        self.mask & R_OK != 0
+    }
+
+    /// Returns whether write permission is requested (`W_OK`).
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn wants_write(&self) -> bool {
+        self.mask & W_OK != 0
+    }
+
+    /// Returns whether execute or search permission is requested (`X_OK`).
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn wants_exec(&self) -> bool {
+        self.mask & X_OK != 0
+    }
+
+    /// Returns whether only the existence of the file is tested (`F_OK`).
+    #[allow(dead_code)]
+    #[inline]
+    #[must_use]
+    pub const fn exists_only(&self) -> bool {
+        self.mask & (R_OK | W_OK | X_OK) == F_OK
+    }
+}
+
 /// FUSE init request input `fuse_init_in`
 #[derive(Debug)]
 #[repr(C)]
@@ -1029,6 +1313,18 @@ pub struct FuseInitIn {
     pub flags: u32,
 }
 
+/// The extension of `fuse_init_in` since ABI 7.36, only sent by the kernel
+/// when the request is long enough to carry it
+// #[cfg(feature = "abi-7-36")]
+#[derive(Debug)]
+#[repr(C)]
+pub struct FuseInitInExt {
+    /// The upper 32 bits of FUSE init flags
+    pub flags2: u32,
+    /// Reserved
+    pub unused: [u32; 11],
+}
+
 /// FUSE init response `fuse_init_out`
 #[derive(Debug)]
 #[repr(C)]
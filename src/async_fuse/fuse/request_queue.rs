@@ -0,0 +1,106 @@
+//! The bounded priority queue of FUSE requests
+
+use std::collections::VecDeque;
+
+use super::fuse_request::RequestPriority;
+
+/// A bounded queue serving requests by [`RequestPriority`], and in arrival
+/// order within a priority.
+///
+/// When the queue is full, a push is rejected and the caller is expected to
+/// apply backpressure, e.g. stop reading the FUSE device until a request is
+/// popped.
+#[derive(Debug)]
+pub struct RequestQueue<T> {
+    /// The queued `High` requests
+    high: VecDeque<T>,
+    /// The queued `Normal` requests
+    normal: VecDeque<T>,
+    /// The queued `Low` requests
+    low: VecDeque<T>,
+    /// The max number of queued requests
+    capacity: usize,
+}
+
+impl<T> RequestQueue<T> {
+    /// Create an empty queue holding at most `capacity` requests
+    #[inline]
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            high: VecDeque::new(),
+            normal: VecDeque::new(),
+            low: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Enqueue a request, or give it back if the queue is full.
+    #[inline]
+    pub fn push(&mut self, priority: RequestPriority, request: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(request);
+        }
+        match priority {
+            RequestPriority::High => self.high.push_back(request),
+            RequestPriority::Normal => self.normal.push_back(request),
+            RequestPriority::Low => self.low.push_back(request),
+        }
+        Ok(())
+    }
+
+    /// Dequeue the earliest request of the highest priority
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.high
+            .pop_front()
+            .or_else(|| self.normal.pop_front())
+            .or_else(|| self.low.pop_front())
+    }
+
+    /// The number of queued requests
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.high.len() + self.normal.len() + self.low.len()
+    }
+
+    /// Whether no request is queued
+    #[allow(dead_code)] // Required by `len_without_is_empty`, used by tests
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the queue holds `capacity` requests
+    #[inline]
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.capacity
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::fuse_request::RequestPriority;
+    use super::RequestQueue;
+
+    #[test]
+    fn high_priority_first() {
+        let mut queue = RequestQueue::new(4);
+        assert_eq!(queue.push(RequestPriority::Low, "read 1"), Ok(()));
+        assert_eq!(queue.push(RequestPriority::Low, "read 2"), Ok(()));
+        assert_eq!(queue.push(RequestPriority::Normal, "lookup"), Ok(()));
+        assert_eq!(queue.push(RequestPriority::High, "interrupt"), Ok(()));
+        assert!(queue.is_full());
+        assert_eq!(queue.push(RequestPriority::High, "forget"), Err("forget"));
+
+        assert_eq!(queue.pop(), Some("interrupt"));
+        assert_eq!(queue.pop(), Some("lookup"));
+        assert_eq!(queue.pop(), Some("read 1"));
+        assert_eq!(queue.pop(), Some("read 2"));
+        assert_eq!(queue.pop(), None);
+        assert!(queue.is_empty());
+    }
+}
@@ -1,5 +1,6 @@
 //! The implementation of FUSE session
 
+use std::ffi::OsStr;
 use std::fs::File;
 use std::io::Read;
 use std::os::fd::FromRawFd;
@@ -11,13 +12,14 @@ use std::time::{Duration, UNIX_EPOCH};
 
 use aligned_utils::bytes::AlignedBytes;
 use anyhow::{anyhow, Context};
-use clippy_utilities::Cast;
+use clippy_utilities::{Cast, OverflowArithmetic};
 use crossbeam_channel::{Receiver, Sender};
 use crossbeam_utils::atomic::AtomicCell;
 use datenlord::common::task_manager::{GcHandle, TaskName, TASK_MANAGER};
 use nix::errno::Errno;
 use nix::sys::stat::SFlag;
 use nix::unistd;
+use parking_lot::Mutex;
 use tokio::runtime::Handle;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, instrument};
@@ -25,20 +27,22 @@ use tracing::{debug, error, info, instrument};
 use super::context::ProtoVersion;
 use super::file_system::FileSystem;
 use super::fuse_reply::{
-    ReplyAttr, ReplyBMap, ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry,
-    ReplyInit, ReplyLock, ReplyOpen, ReplyStatFs, ReplyWrite, ReplyXAttr,
+    InitReplyBuilder, ReplyAttr, ReplyBMap, ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty,
+    ReplyEntry, ReplyInit, ReplyLock, ReplyOpen, ReplyStatFs, ReplyWrite, ReplyXAttr,
+    FUSE_MAX_MAX_WRITE, FUSE_MIN_MAX_WRITE,
 };
-use super::fuse_request::{Operation, Request};
+use super::fuse_request::{Operation, Request, RequestPriority};
+use super::in_flight::{InFlightRequests, RegisterError};
 use super::mount;
 #[cfg(feature = "abi-7-23")]
 use super::protocol::FATTR_CTIME;
 #[cfg(feature = "abi-7-9")]
 use super::protocol::FATTR_LOCKOWNER; // {FATTR_ATIME_NOW, FATTR_MTIME_NOW};
 use super::protocol::{
-    FuseInitIn, FuseInitOut, FuseSetXAttrIn, FATTR_ATIME, FATTR_FH, FATTR_GID, FATTR_MODE,
-    FATTR_MTIME, FATTR_SIZE, FATTR_UID, FUSE_ASYNC_READ, FUSE_KERNEL_MINOR_VERSION,
-    FUSE_KERNEL_VERSION, FUSE_RELEASE_FLUSH,
+    FuseInitIn, FuseSetXAttrIn, FATTR_ATIME, FATTR_FH, FATTR_GID, FATTR_MODE, FATTR_MTIME,
+    FATTR_SIZE, FATTR_UID, FUSE_ASYNC_READ,
 };
+use super::request_queue::RequestQueue;
 use crate::async_fuse::fuse::de::DeserializeError;
 use crate::async_fuse::memfs::{
     CreateParam, FileLockParam, MemFs, MetaData, RenameParam, SetAttrParam,
@@ -49,15 +53,9 @@ use crate::async_fuse::memfs::{
 const INIT_FLAGS: u32 = FUSE_ASYNC_READ;
 // TODO: Add FUSE_EXPORT_SUPPORT and FUSE_BIG_WRITES (requires ABI 7.10)
 
-/// The max size of write requests from the kernel. The absolute minimum is 4k,
-/// FUSE recommends at least 128k, max 16M. The FUSE default is  128k on Linux.
-#[cfg(target_os = "linux")]
-const MAX_WRITE_SIZE: u32 = 128 * 1024;
-
-/// Size of the buffer for reading a request from the kernel. Since the kernel
-/// may send up to `MAX_WRITE_SIZE` bytes in a write request, we use that value
-/// plus some extra space.
-const BUFFER_SIZE: u32 = MAX_WRITE_SIZE + 512;
+/// The extra space of the buffer for reading a request from the kernel, for
+/// the headers of a write request.
+const BUFFER_EXTRA_SIZE: u32 = 512;
 
 /// We use `PAGE_SIZE` (4 KiB) as the alignment of the buffer.
 const PAGE_SIZE: usize = 4096;
@@ -106,8 +104,30 @@ mod _fuse_fd_clone {
 
 use _fuse_fd_clone::fuse_fd_clone;
 
+/// A FUSE request read from the FUSE device but not processed yet
+struct QueuedRequest {
+    /// The buffer holding the request
+    buffer: AlignedBytes,
+    /// The size of the request in `buffer`
+    size: usize,
+    /// The FUSE device file to reply to
+    file: File,
+}
+
+/// The queue of the requests read from the FUSE device, every queued request
+/// holds a buffer of the pool, so it never holds more than `MAX_BACKGROUND`
+/// requests.
+type SharedRequestQueue = Arc<Mutex<RequestQueue<QueuedRequest>>>;
+
+/// Classify a request read from the FUSE device, a malformed one is left to
+/// `process_fuse_request()` to handle.
+fn request_priority(bytes: &[u8], proto_version: ProtoVersion) -> RequestPriority {
+    Request::new(bytes, proto_version)
+        .map_or(RequestPriority::Normal, |req| req.operation().priority())
+}
+
 /// A loop to read requests from FUSE device continuously
-#[allow(clippy::needless_pass_by_value)]
+#[allow(clippy::needless_pass_by_value, clippy::too_many_arguments)]
 fn fuse_device_reader(
     buffer_tx: Sender<(File, AlignedBytes)>,
     buffer_rx: Receiver<(File, AlignedBytes)>,
@@ -115,6 +135,8 @@ fn fuse_device_reader(
     runtime_handle: Handle,
     proto_version: ProtoVersion,
     fs: Arc<dyn FileSystem + Send + Sync>,
+    in_flight: Arc<InFlightRequests>,
+    queue: SharedRequestQueue,
 ) {
     loop {
         let Ok((mut file, mut buffer)) = buffer_rx.recv() else {
@@ -170,15 +192,33 @@ fn fuse_device_reader(
             }
         };
 
+        let priority = buffer.get(..size).map_or(RequestPriority::Normal, |bytes| {
+            request_priority(bytes, proto_version)
+        });
+        queue
+            .lock()
+            .push(priority, QueuedRequest { buffer, size, file })
+            .unwrap_or_else(|_| {
+                unreachable!("The request queue holds at most as many requests as buffers.")
+            });
+
+        // Every read request gets a task, which serves the most urgent queued
+        // request when it starts, so that e.g. an `INTERRUPT` overtakes the
+        // `READ`s read before it.
         let spawn_result = runtime_handle.block_on(fuse_request_spawn_handle.spawn(|_| {
-            process_fuse_request(
-                buffer,
-                size,
-                file,
-                Arc::clone(&fs),
-                buffer_tx.clone(),
-                proto_version,
-            )
+            let queue = Arc::clone(&queue);
+            let fs = Arc::clone(&fs);
+            let buffer_tx = buffer_tx.clone();
+            let in_flight = Arc::clone(&in_flight);
+            async move {
+                // Every task pops after its own push, so the queue is not empty
+                let queued = queue.lock().pop();
+                let Some(QueuedRequest { buffer, size, file }) = queued else {
+                    unreachable!("A request is queued for every spawned task.");
+                };
+                process_fuse_request(buffer, size, file, fs, buffer_tx, proto_version, in_flight)
+                    .await;
+            }
         }));
         if spawn_result.is_err() {
             info!("Try to spawn task of `FuseRequest` after shutdow.");
@@ -195,11 +235,12 @@ async fn process_fuse_request(
     fs: Arc<dyn FileSystem + Send + Sync + 'static>,
     sender: Sender<(File, AlignedBytes)>,
     proto_version: ProtoVersion,
+    in_flight: Arc<InFlightRequests>,
 ) {
     let bytes = byte_buffer
         .get(..read_size)
         .unwrap_or_else(|| panic!("failed to read {read_size} bytes from the buffer",));
-    let fuse_req = match Request::new(bytes, proto_version) {
+    let fuse_req = match Request::new_utf8(bytes, proto_version) {
         // Dispatch request
         Ok(r) => r,
         // Quit on illegal request
@@ -221,11 +262,68 @@ async fn process_fuse_request(
                 return;
             }
 
+            // The filesystem takes names as `str`, reject non-UTF-8 ones
+            if e == DeserializeError::NonUtf8Name {
+                if let Ok(header) = Request::peek_header(bytes) {
+                    error!(
+                        "Reject FUSE request unique={} with a non-UTF-8 name",
+                        header.unique
+                    );
+                    ReplyEmpty::new(header.unique, &mut file)
+                        .error_code(Errno::EINVAL)
+                        .await
+                        .unwrap_or_else(|reply_err| {
+                            panic!("Failed to reply an error code: {reply_err}.")
+                        });
+                    sender.send((file, byte_buffer)).unwrap_or_else(|_| {
+                        error!("The buffer pool is closed.");
+                    });
+                    return;
+                }
+            }
+
             // TODO: graceful handle request build failure
             panic!("failed to build FUSE request, the error is: {e}");
         }
     };
     debug!("received FUSE req={}", fuse_req);
+    let _guard = match in_flight.register(fuse_req.unique()) {
+        Ok(guard) => guard,
+        // The kernel knows only one request per `unique`, replying to a
+        // duplicate would complete the request in flight with a bogus result,
+        // so just drop it.
+        Err(RegisterError::Duplicate) => {
+            error!("Drop FUSE req={fuse_req:?} with a duplicate unique");
+            sender.send((file, byte_buffer)).unwrap_or_else(|_| {
+                error!("The buffer pool is closed.");
+            });
+            return;
+        }
+        Err(RegisterError::Interrupted) => {
+            ReplyEmpty::new(fuse_req.unique(), &mut file)
+                .error_code(Errno::EINTR)
+                .await
+                .unwrap_or_else(|reply_err| panic!("Failed to reply an error code: {reply_err}."));
+            sender.send((file, byte_buffer)).unwrap_or_else(|_| {
+                error!("The buffer pool is closed.");
+            });
+            return;
+        }
+    };
+    // An interrupt may arrive before the request it targets, keep it for the
+    // request then.
+    if let Operation::Interrupt { arg } = *fuse_req.operation() {
+        if !in_flight.interrupt(arg.unique) {
+            debug!(
+                "FUSE request unique={} to interrupt is not read yet",
+                arg.unique
+            );
+            sender.send((file, byte_buffer)).unwrap_or_else(|_| {
+                error!("The buffer pool is closed.");
+            });
+            return;
+        }
+    }
     let res = dispatch(&fuse_req, &mut file, fs).await;
     if let Err(e) = res {
         panic!(
@@ -247,12 +345,17 @@ pub struct Session<F: FileSystem + Send + Sync + 'static> {
     fuse_fd: Arc<FuseFd>,
     /// Kernel FUSE protocol version
     proto_version: AtomicCell<ProtoVersion>,
+    /// The configured max size of write requests, the kernel may negotiate a
+    /// smaller one
+    max_write: u32,
     /// Mount path (relative)
     mount_path: PathBuf,
     /// The underlying FUSE file system
     filesystem: Arc<F>,
     /// A handle to spawn FUSE Resuest tasks
     fuse_request_spawn_handle: GcHandle,
+    /// The FUSE requests under processing
+    in_flight: Arc<InFlightRequests>,
 }
 
 /// FUSE device fd
@@ -281,11 +384,13 @@ impl<F: FileSystem + Send + Sync + 'static> Drop for Session<F> {
     }
 }
 
-/// Create FUSE session
+/// Create FUSE session, `max_write` is the max size of write requests to
+/// negotiate with the kernel
 #[allow(clippy::clone_on_ref_ptr)] // allow this clone to transform trait to sub-trait
 pub async fn new_session_of_memfs<M>(
     mount_path: &Path,
     fs: MemFs<M>,
+    max_write: u32,
 ) -> anyhow::Result<Session<MemFs<M>>>
 where
     M: MetaData + Send + Sync + 'static,
@@ -310,9 +415,11 @@ where
     Ok(Session {
         fuse_fd: Arc::new(FuseFd(fuse_fd)),
         proto_version: AtomicCell::new(ProtoVersion::UNSPECIFIED),
+        max_write,
         mount_path: mount_path.to_owned(),
         fuse_request_spawn_handle,
         filesystem: fsarc,
+        in_flight: Arc::new(InFlightRequests::new()),
     })
 }
 
@@ -323,6 +430,15 @@ impl<F: FileSystem + Send + Sync + 'static> Session<F> {
         self.fuse_fd.0
     }
 
+    /// Size of the buffer for reading a request from the kernel. Since the
+    /// kernel may send up to `max_write` bytes in a write request, we use that
+    /// value plus some extra space.
+    fn buffer_size(&self) -> u32 {
+        self.max_write
+            .clamp(FUSE_MIN_MAX_WRITE, FUSE_MAX_MAX_WRITE)
+            .overflow_add(BUFFER_EXTRA_SIZE)
+    }
+
     /// Run the FUSE session
     #[allow(clippy::arithmetic_side_effects, clippy::pattern_type_mismatch)] // The `select!` macro will generate code that goes against these rules.
     pub async fn run(self, token: CancellationToken) -> anyhow::Result<()> {
@@ -332,16 +448,29 @@ impl<F: FileSystem + Send + Sync + 'static> Session<F> {
             .await
             .context("failed to setup buffer pool")?;
 
+        let queue: SharedRequestQueue =
+            Arc::new(Mutex::new(RequestQueue::new(MAX_BACKGROUND.into())));
         for _ in 0..MAX_FUSE_READER {
+            let queue = Arc::clone(&queue);
             let pool_tx = pool_sender.clone();
             let pool_rx = pool_receiver.clone();
             let gc_handle = self.fuse_request_spawn_handle.clone();
             let handle = Handle::current();
             let fs = Arc::clone(&self.filesystem);
             let protocol_version = self.proto_version.load();
+            let in_flight = Arc::clone(&self.in_flight);
             // The `JoinHandle` is ignored
             thread::spawn(move || {
-                fuse_device_reader(pool_tx, pool_rx, gc_handle, handle, protocol_version, fs);
+                fuse_device_reader(
+                    pool_tx,
+                    pool_rx,
+                    gc_handle,
+                    handle,
+                    protocol_version,
+                    fs,
+                    in_flight,
+                    queue,
+                );
             });
         }
 
@@ -361,7 +490,7 @@ impl<F: FileSystem + Send + Sync + 'static> Session<F> {
             crossbeam_channel::bounded::<(File, AlignedBytes)>(MAX_BACKGROUND.into());
 
         for _ in 0..MAX_BACKGROUND {
-            let buf = AlignedBytes::new_zeroed(BUFFER_SIZE.cast(), PAGE_SIZE);
+            let buf = AlignedBytes::new_zeroed(self.buffer_size().cast(), PAGE_SIZE);
             let session_fd = self.dev_fd();
 
             let file = unsafe {
@@ -395,7 +524,7 @@ impl<F: FileSystem + Send + Sync + 'static> Session<F> {
                 )
             });
             if let Ok(req) = Request::new(bytes, self.proto_version.load()) {
-                if let Operation::Init { arg } = *req.operation() {
+                if let Operation::Init { arg, .. } = *req.operation() {
                     let filesystem = Arc::clone(&self.filesystem);
                     self.init(arg, &req, &*filesystem, &mut file).await?;
                 }
@@ -422,71 +551,38 @@ impl<F: FileSystem + Send + Sync + 'static> Session<F> {
         // https://github.com/libfuse/libfuse/blob/master/lib/fuse_lowlevel.c#L1892
         let reply = ReplyInit::new(req.unique(), file);
         // We don't support ABI versions before 7.8
-        if arg.major < 7 || (arg.major == 7 && arg.minor < 8) {
-            error!("Unsupported FUSE ABI version={}.{}", arg.major, arg.minor);
-            reply.error_code(Errno::EPROTO).await?;
-            return Err(anyhow!("FUSE ABI version too low"));
-        }
+        let init_out = match InitReplyBuilder::new(INIT_FLAGS) // TODO: handle init flags properly
+            .max_write(self.max_write)
+            .max_background(MAX_BACKGROUND)
+            .congestion_threshold(10) // TODO: set congestion threshold
+            .build(arg)
+        {
+            Ok(init_out) => init_out,
+            Err(errno) => {
+                error!("Unsupported FUSE ABI version={}.{}", arg.major, arg.minor);
+                reply.error_code(errno).await?;
+                return Err(anyhow!("FUSE ABI version too low"));
+            }
+        };
         // Call filesystem init method and give it a chance to return an error
         let filesystem = fs;
-        let init_res = filesystem.init(req).await;
+        let init_res = filesystem.init(req, init_out.max_write).await;
         if let Err(err) = init_res {
             reply.error_code(Errno::ENOSYS).await?;
             return Err(anyhow!("user defined init failed, the error is: {}", err,));
         }
-        let flags = arg.flags & INIT_FLAGS; // TODO: handle init flags properly
-        #[cfg(not(feature = "abi-7-13"))]
-        let unused = 0_u32;
-        #[cfg(feature = "abi-7-13")]
-        let congestion_threshold = 10_u16; // TODO: set congestion threshold
-        #[cfg(feature = "abi-7-23")]
-        let time_gran = 1_u32; // TODO: set time_gran
-        #[cfg(all(feature = "abi-7-23", not(feature = "abi-7-28")))]
-        let unused = [0_u32; 9];
-        #[cfg(feature = "abi-7-28")]
-        let max_pages = 0_u16; // TODO: max_pages = (max_write - 1) / getpagesize() + 1;
-        #[cfg(feature = "abi-7-28")]
-        let padding = 0_u16;
-        #[cfg(feature = "abi-7-28")]
-        let unused = [0_u32; 8];
-        // Reply with our desired version and settings. If the kernel supports a
-        // larger major version, it'll re-send a matching init message. If it
-        // supports only lower major versions, we replied with an error above.
-        reply
-            .init(FuseInitOut {
-                major: FUSE_KERNEL_VERSION,
-                minor: FUSE_KERNEL_MINOR_VERSION, /* Do not change minor version, otherwise
-                                                   * unknown panic */
-                max_readahead: arg.max_readahead, // accept FUSE kernel module max_readahead
-                flags,                            /* TODO: use features given in INIT_FLAGS and
-                                                   * reported as capable */
-                #[cfg(not(feature = "abi-7-13"))]
-                unused,
-                #[cfg(feature = "abi-7-13")]
-                max_background: MAX_BACKGROUND,
-                #[cfg(feature = "abi-7-13")]
-                congestion_threshold,
-                max_write: MAX_WRITE_SIZE,
-                #[cfg(feature = "abi-7-23")]
-                time_gran,
-                #[cfg(all(feature = "abi-7-23", not(feature = "abi-7-28")))]
-                unused,
-                #[cfg(feature = "abi-7-28")]
-                max_pages,
-                #[cfg(feature = "abi-7-28")]
-                padding,
-                #[cfg(feature = "abi-7-28")]
-                unused,
-            })
-            .await?;
         debug!(
             "INIT response: ABI version={}.{}, flags={:#x}, max readahead={}, max write={}",
-            FUSE_KERNEL_VERSION,
-            FUSE_KERNEL_MINOR_VERSION,
-            flags,
-            arg.max_readahead,
-            MAX_WRITE_SIZE,
+            init_out.major,
+            init_out.minor,
+            init_out.flags,
+            init_out.max_readahead,
+            init_out.max_write,
         );
+        // Reply with our desired version and settings. If the kernel supports a
+        // larger major version, it'll re-send a matching init message. If it
+        // supports only lower major versions, we replied with an error above.
+        reply.init(init_out).await?;
 
         // Store the kernel FUSE major and minor version
         self.proto_version.store(ProtoVersion {
@@ -526,13 +622,13 @@ async fn dispatch<'a>(
 
         Operation::Lookup { name } => {
             let reply = ReplyEntry::new(req.unique(), file);
-            fs.lookup(req, req.nodeid(), name, reply).await
+            fs.lookup(req, req.nodeid(), utf8_name(name), reply).await
         }
         Operation::Forget { arg } => {
             fs.forget(req, arg.nlookup).await; // No reply
             Ok(0)
         }
-        Operation::GetAttr => {
+        Operation::GetAttr { .. } => {
             let reply = ReplyAttr::new(req.unique(), file);
             fs.getattr(req, reply).await
         }
@@ -618,7 +714,7 @@ async fn dispatch<'a>(
         Operation::MkNod { arg, name } => {
             let param = CreateParam {
                 parent: req.nodeid(),
-                name: name.to_owned(),
+                name: utf8_name(name).to_owned(),
                 mode: arg.mode,
                 rdev: arg.rdev,
                 uid: req.uid(),
@@ -631,19 +727,20 @@ async fn dispatch<'a>(
         }
         Operation::MkDir { arg, name } => {
             let reply = ReplyEntry::new(req.unique(), file);
-            fs.mkdir(req, req.nodeid(), name, arg.mode, reply).await
+            fs.mkdir(req, req.nodeid(), utf8_name(name), arg.mode, reply)
+                .await
         }
         Operation::Unlink { name } => {
             let reply = ReplyEmpty::new(req.unique(), file);
-            fs.unlink(req, req.nodeid(), name, reply).await
+            fs.unlink(req, req.nodeid(), utf8_name(name), reply).await
         }
         Operation::RmDir { name } => {
             let reply = ReplyEmpty::new(req.unique(), file);
-            fs.rmdir(req, req.nodeid(), name, reply).await
+            fs.rmdir(req, req.nodeid(), utf8_name(name), reply).await
         }
         Operation::SymLink { name, link } => {
             let reply = ReplyEntry::new(req.unique(), file);
-            fs.symlink(req, req.nodeid(), name, Path::new(link), reply)
+            fs.symlink(req, req.nodeid(), utf8_name(name), Path::new(link), reply)
                 .await
         }
         Operation::Rename {
@@ -654,16 +751,16 @@ async fn dispatch<'a>(
             let reply = ReplyEmpty::new(req.unique(), file);
             let param = RenameParam {
                 old_parent: req.nodeid(),
-                old_name: oldname.to_owned(),
+                old_name: utf8_name(oldname).to_owned(),
                 new_parent: arg.newdir,
-                new_name: newname.to_owned(),
+                new_name: utf8_name(newname).to_owned(),
                 flags: 0,
             };
             fs.rename(req, param, reply).await
         }
         Operation::Link { arg, name } => {
             let reply = ReplyEntry::new(req.unique(), file);
-            fs.link(req, arg.oldnodeid, name, reply).await
+            fs.link(req, arg.oldnodeid, utf8_name(name), reply).await
         }
         Operation::Open { arg } => {
             let reply = ReplyOpen::new(req.unique(), file);
@@ -693,7 +790,7 @@ async fn dispatch<'a>(
             fs.flush(req, arg.fh, arg.lock_owner, reply).await
         }
         Operation::Release { arg } => {
-            let flush = !matches!(arg.release_flags & FUSE_RELEASE_FLUSH, 0);
+            let flush = arg.should_flush();
             let reply = ReplyEmpty::new(req.unique(), file);
             fs.release(req, arg.fh, arg.flags, arg.lock_owner, flush, reply)
                 .await
@@ -734,12 +831,19 @@ async fn dispatch<'a>(
             }
             assert!(value.len() == arg.size.cast::<usize>());
             let reply = ReplyEmpty::new(req.unique(), file);
-            fs.setxattr(req, name, value, arg.flags, get_position(arg), reply)
-                .await
+            fs.setxattr(
+                req,
+                utf8_name(name),
+                value,
+                arg.flags,
+                get_position(arg),
+                reply,
+            )
+            .await
         }
         Operation::GetXAttr { arg, name } => {
             let reply = ReplyXAttr::new(req.unique(), file);
-            fs.getxattr(req, name, arg.size, reply).await
+            fs.getxattr(req, utf8_name(name), arg.size, reply).await
         }
         Operation::ListXAttr { arg } => {
             let reply = ReplyXAttr::new(req.unique(), file);
@@ -747,7 +851,7 @@ async fn dispatch<'a>(
         }
         Operation::RemoveXAttr { name } => {
             let reply = ReplyEmpty::new(req.unique(), file);
-            fs.removexattr(req, name, reply).await
+            fs.removexattr(req, utf8_name(name), reply).await
         }
         Operation::Access { arg } => {
             let reply = ReplyEmpty::new(req.unique(), file);
@@ -755,8 +859,15 @@ async fn dispatch<'a>(
         }
         Operation::Create { arg, name } => {
             let reply = ReplyCreate::new(req.unique(), file);
-            fs.create(req, req.nodeid(), name, arg.mode, arg.flags, reply)
-                .await
+            fs.create(
+                req,
+                req.nodeid(),
+                utf8_name(name),
+                arg.mode,
+                arg.flags,
+                reply,
+            )
+            .await
         }
         Operation::GetLk { arg } => {
             let reply = ReplyLock::new(req.unique(), file);
@@ -845,9 +956,9 @@ async fn dispatch<'a>(
             let reply = ReplyEmpty::new(req.unique(), file);
             let param = RenameParam {
                 old_parent: req.nodeid(),
-                old_name: oldname.to_owned(),
+                old_name: utf8_name(oldname).to_owned(),
                 new_parent: arg.newdir,
-                new_name: newname.to_owned(),
+                new_name: utf8_name(newname).to_owned(),
                 flags: arg.flags,
             };
             fs.rename(req, param, reply).await
@@ -866,11 +977,22 @@ async fn dispatch<'a>(
         Operation::CuseInit { arg } => {
             panic!("unsupported CuseInit arg={arg:?}");
         }
+        Operation::Unknown { opcode, data } => {
+            error!("Unknown opcode={}, data.len={}", opcode, data.len());
+            not_implement_helper(req, file).await
+        }
     };
 
     result
 }
 
+/// Returns a name of a request parsed by [`Request::new_utf8`]
+fn utf8_name(name: &OsStr) -> &str {
+    name.to_str().unwrap_or_else(|| {
+        unreachable!("the name {name:?} should have been checked to be UTF-8 when parsed")
+    })
+}
+
 /// Replies ENOSYS
 async fn not_implement_helper(req: &Request<'_>, file: &mut File) -> nix::Result<usize> {
     let reply = ReplyEmpty::new(req.unique(), file);
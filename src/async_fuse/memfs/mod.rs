@@ -57,6 +57,8 @@ pub struct MemFs<M: MetaData + Send + Sync + 'static> {
     metadata: Arc<M>,
     /// Storage manager
     storage: StorageType,
+    /// The size of storage blocks
+    block_size: usize,
 }
 
 /// Set attribute parameters
@@ -182,7 +184,11 @@ impl<M: MetaData + Send + Sync + 'static> MemFs<M> {
             mount_point, capacity, node_id, storage_config
         );
         let metadata = M::new(kv_engine, node_id).await?;
-        Ok(Self { metadata, storage })
+        Ok(Self {
+            metadata,
+            storage,
+            block_size: storage_config.block_size,
+        })
     }
 }
 
@@ -192,8 +198,20 @@ impl<M: MetaData + Send + Sync + 'static> FileSystem for MemFs<M> {
 
     /// Initialize filesystem.
     /// Called before any other filesystem method.
-    async fn init(&self, req: &Request<'_>) -> nix::Result<()> {
-        debug!("init(req={:?}), cache size={}", req, 0_i32);
+    async fn init(&self, req: &Request<'_>, max_write: u32) -> nix::Result<()> {
+        debug!(
+            "init(req={:?}), cache size={}, max_write={}",
+            req, 0_i32, max_write
+        );
+        // The kernel may negotiate a smaller `max_write` than the configured
+        // one, then a write request may span two blocks
+        if self.block_size.overflow_rem(max_write.cast()) != 0 {
+            warn!(
+                "the max write size {} negotiated with the kernel is not a divisor of \
+                    the block size {}, writes may span blocks",
+                max_write, self.block_size,
+            );
+        }
         Ok(())
     }
 
@@ -275,7 +293,18 @@ impl<M: MetaData + Send + Sync + 'static> FileSystem for MemFs<M> {
 
         match self.metadata.open(context, ino, flags).await {
             Ok(fd) => {
-                self.storage.open(ino, fd.cast(), flags.into());
+                if let Err(e) = self.storage.open(ino, fd.cast(), flags.into()) {
+                    debug!("open() failed, the error is: {:?}", e);
+                    // The open error is replied even if the cleanup fails
+                    if let Err(release_err) = self.metadata.release(ino, fd, flags, 0, false).await
+                    {
+                        error!(
+                            "open() failed to release fd={} of ino={}, the error is: {:?}",
+                            fd, ino, release_err
+                        );
+                    }
+                    return reply.error(e.into()).await;
+                }
                 reply.opened(fd, flags).await
             }
             Err(e) => {
@@ -297,16 +326,15 @@ impl<M: MetaData + Send + Sync + 'static> FileSystem for MemFs<M> {
     async fn forget(&self, req: &Request<'_>, nlookup: u64) {
         let _timer = FILESYSTEM_METRICS.start_storage_operation_timer("forget");
         let ino = req.nodeid();
-        let deleted = self
-            .metadata
-            .forget(ino, nlookup)
-            .await
-            .unwrap_or_else(|e| panic!("{e}"));
-        if deleted {
-            self.storage
-                .remove(ino)
-                .await
-                .unwrap_or_else(|e| panic!("{e}"));
+        // FORGET has no reply, so the errors can only be logged
+        match self.metadata.forget(ino, nlookup).await {
+            Ok(true) => {
+                if let Err(e) = self.storage.remove(ino).await {
+                    error!("forget() failed to remove the data of ino={ino}, the error is: {e:?}");
+                }
+            }
+            Ok(false) => {}
+            Err(e) => error!("forget() failed for ino={ino}, the error is: {e:?}"),
         }
     }
 
@@ -543,7 +571,7 @@ impl<M: MetaData + Send + Sync + 'static> FileSystem for MemFs<M> {
             }
         };
 
-        if offset >= file_size {
+        if size == 0 || offset >= file_size {
             return reply.data(Vec::<u8>::new()).await;
         }
 
@@ -583,6 +611,12 @@ impl<M: MetaData + Send + Sync + 'static> FileSystem for MemFs<M> {
         let ino = req.nodeid();
         let data_len: u64 = data.len().cast();
 
+        // A zero-length write succeeds without touching the data, and must not
+        // extend the file even if `offset` is beyond its end.
+        if data_len == 0 {
+            return reply.written(0).await;
+        }
+
         let (old_size, _) = self.metadata.mtime_and_size(ino);
         let result = self.storage.write(ino, fh, offset.cast(), &data).await;
 
@@ -285,15 +285,17 @@ impl MetaData for S3MetaData {
         let (res, retry) = retry_txn!(TXN_RETRY_LIMIT, {
             let mut txn = self.kv_engine.new_meta_txn().await;
             let mut result = false;
-            let inode = self.get_inode_from_txn(txn.as_mut(), ino).await?;
+            // The node may be gone already, e.g. it was removed by `unlink()`
+            // while the kernel held no reference, so forgetting it is a no-op.
+            let Some(inode) = self.try_get_inode_from_txn(txn.as_mut(), ino).await? else {
+                return Ok(false);
+            };
             inode.dec_lookup_count_by(nlookup);
-            let is_deleted = inode.get_lookup_count() == 0;
+            // Only an unlinked node is removed once the kernel forgets it. Its
+            // dir entry was already removed by `unlink()`, and the name may
+            // have been reused since, so the dir entry is left untouched.
+            let is_deleted = inode.get_lookup_count() <= 0 && inode.is_deferred_deletion();
             if is_deleted {
-                // FIXME: rename should also rename the node's name and reset the parent ino
-                txn.delete(&KeyType::DirEntryKey((
-                    inode.get_parent_ino(),
-                    inode.get_name().to_owned(),
-                )));
                 txn.delete(&KeyType::INum2Node(ino));
                 result = true;
             } else {
@@ -526,7 +528,9 @@ impl MetaData for S3MetaData {
             let new_node = parent_node
                 .create_child_node(&param, new_num, txn.as_mut())
                 .await?;
-            let fuse_attr = fs_util::convert_to_fuse_attr(new_node.get_attr());
+            // The returned entry counts as a lookup, which the kernel drops
+            // with `forget()` later
+            let fuse_attr = fs_util::convert_to_fuse_attr(new_node.lookup_attr());
             let ttl = Duration::new(MY_TTL_SEC, 0);
             txn.set(
                 &KeyType::INum2Node(new_num),
@@ -574,7 +578,9 @@ impl MetaData for S3MetaData {
 
             let child_ino = child_entry.ino();
             let child_node = self.get_inode_from_txn(txn.as_mut(), child_ino).await?;
-            let child_attr = child_node.get_attr();
+            // The kernel increases its lookup count of the child for every
+            // successful lookup, and decreases it with `forget()`
+            let child_attr = child_node.lookup_attr();
 
             let ttl = Duration::new(MY_TTL_SEC, 0);
             let fuse_attr = fs_util::convert_to_fuse_attr(child_attr);
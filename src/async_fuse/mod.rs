@@ -8,7 +8,10 @@ use tokio_util::sync::CancellationToken;
 
 use self::memfs::kv_engine::KVEngineType;
 use crate::async_fuse::fuse::session;
-use crate::new_storage::{BackendBuilder, MemoryCache, StorageManager};
+use crate::new_storage::{
+    BackendBuilder, CircuitBreakerBackend, CircuitBreakerPolicy, MemoryCache, RetryBackend,
+    RetryPolicy, StorageManager,
+};
 use crate::AsyncFuseArgs;
 
 pub mod fuse;
@@ -35,7 +38,19 @@ pub async fn start_async_fuse(
         let capacity_in_blocks = memory_cache_config.capacity.overflow_div(block_size);
 
         let cache = Arc::new(Mutex::new(MemoryCache::new(capacity_in_blocks, block_size)));
-        let backend = Arc::new(BackendBuilder::new(storage_param.clone()).build().await?);
+        let builder = BackendBuilder::new(storage_param.clone());
+        let backend = if storage_config.skip_health_check {
+            builder.build().await?
+        } else {
+            builder.build_checked().await?
+        };
+        let backend = Arc::new(CircuitBreakerBackend::new(
+            RetryBackend::new(backend, RetryPolicy::default()),
+            CircuitBreakerPolicy {
+                failure_threshold: storage_config.circuit_breaker_failure_threshold,
+                open_duration: storage_config.circuit_breaker_open_duration,
+            },
+        ));
         StorageManager::new(cache, backend, block_size)
     };
 
@@ -49,7 +64,7 @@ pub async fn start_async_fuse(
     )
     .await?;
 
-    let ss = session::new_session_of_memfs(mount_point, fs).await?;
+    let ss = session::new_session_of_memfs(mount_point, fs, storage_config.max_write).await?;
     ss.run(token).await?;
 
     Ok(())
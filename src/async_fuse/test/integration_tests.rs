@@ -1,7 +1,8 @@
 use std::fs::File;
 use std::io::Write;
-use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
 use std::path::Path;
+use std::time::Duration;
 use std::{fs, io, iter};
 
 use anyhow::Context;
@@ -12,6 +13,7 @@ use nix::unistd::{self, Whence};
 use tracing::info;
 
 use super::test_util;
+use crate::async_fuse::memfs::kv_engine::{KVEngine, KVEngineType, KeyType};
 
 pub const BENCH_MOUNT_DIR: &str = "/tmp/datenlord_bench_dir";
 pub const S3_BENCH_MOUNT_DIR: &str = "./s3_fuse_bench";
@@ -747,6 +749,144 @@ fn test_write_read_only_file(mount_dir: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[cfg(test)]
+fn test_sequential_read(mount_dir: &Path) -> anyhow::Result<()> {
+    use std::io::Read;
+
+    info!("test sequential read");
+    let file_path = Path::new(mount_dir).join("test_sequential_read.txt");
+    let repeat_times = 1024;
+    let content: Vec<u8> = iter::repeat(FILE_CONTENT.as_bytes())
+        .take(repeat_times)
+        .flatten()
+        .copied()
+        .collect();
+    fs::write(&file_path, &content)?;
+
+    // Read the file back in small chunks, so that it is served by many
+    // consecutive `READ` requests
+    let mut file = File::open(&file_path)?;
+    let mut read_back = Vec::with_capacity(content.len());
+    let mut chunk = [0_u8; 1000];
+    loop {
+        let size = file.read(&mut chunk)?;
+        if size == 0 {
+            break;
+        }
+        read_back.extend_from_slice(
+            chunk
+                .get(..size)
+                .unwrap_or_else(|| panic!("read {size} bytes into a buffer of 1000 bytes")),
+        );
+    }
+    assert_eq!(read_back, content);
+
+    fs::remove_file(&file_path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+fn test_overwrite_read_back(mount_dir: &Path) -> anyhow::Result<()> {
+    use std::io::{Seek, SeekFrom};
+
+    info!("test overwrite then read back");
+    let file_path = Path::new(mount_dir).join("test_overwrite_read_back.txt");
+    let repeat_times = 1024;
+    let mut content: Vec<u8> = iter::repeat(FILE_CONTENT.as_bytes())
+        .take(repeat_times)
+        .flatten()
+        .copied()
+        .collect();
+    fs::write(&file_path, &content)?;
+
+    // Overwrite a range in the middle of the file
+    let offset = 4000_u64;
+    let patch = b"overwritten";
+    let mut file = File::options().write(true).open(&file_path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(patch)?;
+    drop(file);
+
+    let start: usize = offset.try_into()?;
+    content
+        .get_mut(start..start.overflow_add(patch.len()))
+        .unwrap_or_else(|| panic!("the overwritten range is out of the file"))
+        .copy_from_slice(patch);
+    let read_back = fs::read(&file_path)?;
+    assert_eq!(read_back.len(), content.len());
+    assert_eq!(read_back, content);
+
+    fs::remove_file(&file_path)?;
+    Ok(())
+}
+
+/// Test forget evicts an unlinked node once it is closed, but keeps a linked
+/// one, dropping the kernel caches needs root privilege
+#[cfg(target_os = "linux")]
+#[cfg(test)]
+async fn test_forget(mount_dir: &Path) -> anyhow::Result<()> {
+    use std::io::Read;
+
+    info!("test forget evicts unlinked nodes only");
+    let kv_engine = KVEngineType::new(vec![test_util::TEST_ETCD_ENDPOINT.to_owned()]).await?;
+    let linked_path = Path::new(mount_dir).join("forget_linked.txt");
+    let unlinked_path = Path::new(mount_dir).join("forget_unlinked.txt");
+    fs::write(&linked_path, FILE_CONTENT)?;
+    fs::write(&unlinked_path, FILE_CONTENT)?;
+
+    // Unlink one of the files while it is held open, so that its node is only
+    // marked for deferred deletion.
+    let linked_ino = fs::metadata(&linked_path)?.ino();
+    let mut unlinked_file = File::open(&unlinked_path)?;
+    let unlinked_ino = unlinked_file.metadata()?.ino();
+    fs::remove_file(&unlinked_path)?;
+    assert!(
+        kv_engine
+            .get(&KeyType::INum2Node(unlinked_ino))
+            .await?
+            .is_some(),
+        "the unlinked node {unlinked_ino} should be kept while it is open",
+    );
+    let mut content = String::new();
+    unlinked_file.read_to_string(&mut content)?;
+    assert_eq!(content, FILE_CONTENT);
+
+    // Close the file and drop the unused dentries and inodes, so that the
+    // kernel forgets both nodes.
+    drop(unlinked_file);
+    fs::write("/proc/sys/vm/drop_caches", "2")?;
+
+    let mut evicted = false;
+    for _ in 0..30_u32 {
+        if kv_engine
+            .get(&KeyType::INum2Node(unlinked_ino))
+            .await?
+            .is_none()
+        {
+            evicted = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(
+        evicted,
+        "the unlinked node {unlinked_ino} should have been evicted on forget",
+    );
+
+    assert!(
+        kv_engine
+            .get(&KeyType::INum2Node(linked_ino))
+            .await?
+            .is_some(),
+        "the linked node {linked_ino} should be kept on forget",
+    );
+    let content = fs::read_to_string(&linked_path)?;
+    assert_eq!(content, FILE_CONTENT);
+
+    fs::remove_file(&linked_path)?;
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_all() -> anyhow::Result<()> {
     run_test().await
@@ -793,6 +933,10 @@ async fn _run_test(mount_dir_str: &str, is_s3: bool) -> anyhow::Result<()> {
     test_create_file(mount_dir).context("test_create_file() failed")?;
     test_open_file_permission(mount_dir).context("test_open_file_permission() failed")?;
     test_write_read_only_file(mount_dir).context("test_write_read_only_file() failed")?;
+    test_sequential_read(mount_dir).context("test_sequential_read() failed")?;
+    test_overwrite_read_back(mount_dir).context("test_overwrite_read_back() failed")?;
+    #[cfg(target_os = "linux")]
+    test_forget(mount_dir).await.context("test_forget() failed")?;
 
     test_util::teardown(mount_dir).await?;
 
@@ -57,6 +57,10 @@ fn test_storage_config(is_s3: bool) -> StorageConfig {
             write_back_dirty_limit: 10,
         },
         params,
+        skip_health_check: false,
+        max_write: 0x2_0000,
+        circuit_breaker_failure_threshold: 5,
+        circuit_breaker_open_duration: Duration::from_secs(30),
     }
 }
 
@@ -89,7 +93,7 @@ async fn run_fs(mount_point: &Path, is_s3: bool, token: CancellationToken) -> an
         storage,
     )
     .await?;
-    let ss = session::new_session_of_memfs(mount_point, fs).await?;
+    let ss = session::new_session_of_memfs(mount_point, fs, storage_config.max_write).await?;
     ss.run(token).await?;
 
     Ok(())
@@ -77,6 +77,35 @@ pub struct StorageConfig {
     )]
     /// The root of FS backend
     pub fs_storage_root: String,
+    /// A flag whether to skip the health check of the storage backend at
+    /// startup, default is false
+    #[clap(long = "storage-skip-health-check")]
+    pub skip_health_check: bool,
+    /// The max size of write requests from the kernel, default is 128 KiB.
+    /// It must be a divisor of the block size, so that a write request never
+    /// spans two blocks.
+    #[clap(
+        long = "storage-max-write",
+        value_name = "VALUE",
+        default_value_t = 0x2_0000
+    )]
+    pub max_write: u32,
+    /// The number of consecutive backend failures to open the circuit
+    /// breaker, default is 5
+    #[clap(
+        long = "storage-circuit-breaker-failure-threshold",
+        value_name = "VALUE",
+        default_value_t = 5
+    )]
+    pub circuit_breaker_failure_threshold: u32,
+    /// How long the circuit breaker stays open before probing the backend
+    /// again (in ms), default is 30 s
+    #[clap(
+        long = "storage-circuit-breaker-open-duration",
+        value_name = "VALUE",
+        default_value_t = 30_000
+    )]
+    pub circuit_breaker_open_duration: u64,
 }
 
 /// Memory cache config
@@ -411,6 +440,13 @@ mod tests {
             InnerStorageParams::S3(_) => panic!("storage params should be Fs"),
         }
         assert_eq!(storage_config.block_size, 0x8_0000);
+        assert!(!storage_config.skip_health_check);
+        assert_eq!(storage_config.max_write, 0x2_0000);
+        assert_eq!(storage_config.circuit_breaker_failure_threshold, 5);
+        assert_eq!(
+            storage_config.circuit_breaker_open_duration,
+            Duration::from_secs(30)
+        );
 
         let memory_cache_config = storage_config.memory_cache_config;
         assert_eq!(memory_cache_config.capacity, 0x2_0000_0000);
@@ -459,11 +495,13 @@ mod tests {
             "test_secret_key",
             "--storage-s3-bucket",
             "test_bucket",
+            "--storage-skip-health-check",
             "--kv-server-list",
             "127.0.0.1:7890,127.0.0.1:7891",
         ];
         let config: InnerConfig = Config::parse_from(args).try_into().unwrap();
         let storage_config = config.storage;
+        assert!(storage_config.skip_health_check);
         match storage_config.params {
             InnerStorageParams::S3(s3_config) => {
                 assert_eq!(s3_config.endpoint_url, "http://127.0.0.1:9000");
@@ -570,6 +608,43 @@ mod tests {
         assert!(config.is_err());
     }
 
+    #[test]
+    #[allow(clippy::assertions_on_result_states)]
+    fn test_invalid_max_write() {
+        let build_args = |max_write: &'static str| {
+            vec![
+                "datenlord",
+                "--role",
+                "node",
+                "--node-name",
+                "node1",
+                "--node-ip",
+                "127.0.0.1",
+                "--mount-path",
+                "/tmp/datenlord_data_dir",
+                "--kv-server-list",
+                "127.0.0.1:7890,127.0.0.1:7891",
+                "--csi-endpoint",
+                "unix:///tmp/node.sock ",
+                "--csi-driver-name",
+                "io.datenlord.csi.plugin",
+                "--storage-max-write",
+                max_write,
+                "--csi-worker-port",
+                "9001",
+            ]
+        };
+
+        let config: InnerConfig = Config::parse_from(build_args("65536")).try_into().unwrap();
+        assert_eq!(config.storage.max_write, 0x1_0000);
+
+        let config: Result<InnerConfig, _> = Config::parse_from(build_args("0")).try_into();
+        assert!(config.is_err());
+
+        let config: Result<InnerConfig, _> = Config::parse_from(build_args("100000")).try_into();
+        assert!(config.is_err());
+    }
+
     #[test]
     #[allow(clippy::indexing_slicing)]
     fn test_csi_controller_config() {
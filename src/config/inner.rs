@@ -3,6 +3,7 @@ use std::num::NonZeroUsize;
 use std::str::FromStr;
 use std::time::Duration;
 
+use clippy_utilities::{Cast, OverflowArithmetic};
 use serde::{Deserialize, Serialize};
 use tracing::level_filters::LevelFilter as Level;
 
@@ -144,6 +145,16 @@ pub struct StorageConfig {
     pub memory_cache_config: MemoryCacheConfig,
     /// Storage params
     pub params: StorageParams,
+    /// Whether to skip the health check of the storage backend at startup
+    pub skip_health_check: bool,
+    /// The max size of write requests from the kernel, a divisor of
+    /// `block_size`
+    pub max_write: u32,
+    /// The number of consecutive backend failures to open the circuit breaker
+    pub circuit_breaker_failure_threshold: u32,
+    /// How long the circuit breaker stays open before probing the backend
+    /// again
+    pub circuit_breaker_open_duration: Duration,
 }
 
 impl TryFrom<SuperStorageConfig> for StorageConfig {
@@ -165,10 +176,24 @@ impl TryFrom<SuperStorageConfig> for StorageConfig {
             }
         };
         let block_size = value.block_size;
+        let max_write = value.max_write;
+        if max_write == 0 || block_size.overflow_rem(max_write.cast()) != 0 {
+            return Err(DatenLordError::ArgumentInvalid {
+                context: vec![format!(
+                    "max write {max_write} is not a divisor of block size {block_size}"
+                )],
+            });
+        }
         Ok(StorageConfig {
             block_size,
             memory_cache_config,
             params,
+            skip_health_check: value.skip_health_check,
+            max_write,
+            circuit_breaker_failure_threshold: value.circuit_breaker_failure_threshold,
+            circuit_breaker_open_duration: Duration::from_millis(
+                value.circuit_breaker_open_duration,
+            ),
         })
     }
 }
@@ -1,4 +1,46 @@
 //! Metrics for storage (besides of cache).
 //!
-//! This module is unused for now, and the metrics of storage
-//! are delegated to `opendal::layers::PrometheusLayer`.
+//! The metrics of backend operations are delegated to
+//! `opendal::layers::PrometheusLayer`.
+
+use clippy_utilities::Cast;
+use once_cell::sync::Lazy;
+use prometheus::{register_int_gauge_with_registry, IntGauge, Registry};
+
+use super::DATENLORD_REGISTRY;
+
+/// The storage related metrics.
+pub static STORAGE_METRICS: Lazy<StorageMetrics> =
+    Lazy::new(|| StorageMetrics::new(&DATENLORD_REGISTRY));
+
+/// The storage related metrics.
+#[derive(Debug)]
+pub struct StorageMetrics {
+    /// The number of open file handles.
+    open_handles: IntGauge,
+}
+
+impl StorageMetrics {
+    /// Creates an instance of `StorageMetrics`, which will create an
+    /// `IntGauge` and register it into the specified registry.
+    ///
+    /// # Panics
+    /// This method panics if it called multiple times on the same registry.
+    #[allow(clippy::expect_used)] // We can ensure that this method won't panic if we followed the hints above
+    #[allow(clippy::ignored_unit_patterns)] // Raised by `register_int_gauge_with_registry`
+    fn new(registry: &Registry) -> Self {
+        let open_handles = register_int_gauge_with_registry!(
+            "storage_open_handles",
+            "The number of open file handles",
+            registry,
+        )
+        .expect("Metrics name must be unique.");
+
+        Self { open_handles }
+    }
+
+    /// Sets the number of open file handles.
+    pub fn set_open_handles(&self, count: usize) {
+        self.open_handles.set(count.cast());
+    }
+}
@@ -3,7 +3,8 @@
 use async_trait::async_trait;
 use datenlord::config::{StorageParams, StorageS3Config};
 use datenlord::metrics::DATENLORD_REGISTRY;
-use opendal::layers::{ConcurrentLimitLayer, PrometheusLayer, RetryLayer};
+use futures::StreamExt;
+use opendal::layers::{ConcurrentLimitLayer, PrometheusLayer};
 use opendal::raw::oio::ReadExt;
 use opendal::services::{Fs, Memory, S3};
 use opendal::{ErrorKind, Operator};
@@ -11,6 +12,7 @@ use prometheus::{exponential_buckets, linear_buckets};
 use tokio::io::AsyncWriteExt;
 
 use super::{Backend, StorageResult};
+use crate::new_storage::StorageError;
 
 /// A builder to build `BackendWrapper`.
 #[derive(Debug)]
@@ -74,12 +76,11 @@ impl BackendBuilder {
 
                 let conncurrency_layer =
                     ConcurrentLimitLayer::new(valid_max_concurrent_requests.to_owned());
-                let retry_layer = RetryLayer::new();
 
+                // Transient errors are retried by `RetryBackend`.
                 Operator::new(builder)?
                     .layer(layer)
                     .layer(conncurrency_layer)
-                    .layer(retry_layer)
                     .finish()
             }
             StorageParams::Fs(ref root) => {
@@ -91,6 +92,17 @@ impl BackendBuilder {
 
         Ok(BackendImpl::new(operator))
     }
+
+    /// Build the backend and run a [`Backend::health_check`] on it, so that an
+    /// unreachable or misconfigured backend fails fast.
+    ///
+    /// Use [`BackendBuilder::build`] to skip the check, e.g. in offline tests
+    /// or when `--storage-skip-health-check` is set.
+    pub async fn build_checked(self) -> StorageResult<BackendImpl> {
+        let backend = self.build().await?;
+        backend.health_check().await?;
+        Ok(backend)
+    }
 }
 
 /// The `BackendImpl` struct represents a backend storage system that implements
@@ -133,6 +145,7 @@ impl Backend for BackendImpl {
                     if e.kind() == ErrorKind::NotFound {
                         break;
                     }
+                    return Err(e.into());
                 }
             }
         }
@@ -160,6 +173,23 @@ impl Backend for BackendImpl {
         self.operator.remove_all(dir).await?;
         Ok(())
     }
+
+    /// Checks that the storage system is reachable, by listing the root.
+    ///
+    /// Unlike reading a missing probe object, listing fails if e.g. the bucket
+    /// does not exist, so any error is reported as
+    /// [`StorageError::Unavailable`].
+    #[inline]
+    async fn health_check(&self) -> StorageResult<()> {
+        let unavailable =
+            |e: opendal::Error| StorageError::Unavailable(format!("failed to list the root: {e}"));
+        let mut lister = self.operator.lister("/").await.map_err(unavailable)?;
+        // Only the first page is fetched.
+        if let Some(Err(e)) = lister.next().await {
+            return Err(unavailable(e));
+        }
+        Ok(())
+    }
 }
 
 /// Creates a new `BackendImpl` instance with a memory backend.
@@ -209,4 +239,24 @@ mod tests {
         let size = backend.read("a/2", &mut buf).await.unwrap();
         assert_eq!(size, 0);
     }
+
+    #[tokio::test]
+    #[allow(clippy::assertions_on_result_states)]
+    async fn test_build_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_str().unwrap().to_owned();
+        let backend = BackendBuilder::new(StorageParams::Fs(root))
+            .build_checked()
+            .await
+            .unwrap();
+        test_backend(backend).await;
+
+        // A regular file cannot be the root.
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let root = file.path().to_str().unwrap().to_owned();
+        let res = BackendBuilder::new(StorageParams::Fs(root))
+            .build_checked()
+            .await;
+        assert!(res.is_err());
+    }
 }
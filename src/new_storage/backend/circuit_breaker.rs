@@ -0,0 +1,284 @@
+//! A backend wrapper that stops calling an unavailable backend for a while.
+
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use tracing::{info, warn};
+
+use super::{Backend, StorageResult};
+use crate::new_storage::StorageError;
+
+/// The policy of a [`CircuitBreakerBackend`].
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitBreakerPolicy {
+    /// The number of consecutive failures to open the breaker
+    pub failure_threshold: u32,
+    /// How long the breaker stays open before probing the backend again
+    pub open_duration: Duration,
+}
+
+impl Default for CircuitBreakerPolicy {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The state of a circuit breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BreakerState {
+    /// Calls go to the backend, counting the consecutive failures.
+    Closed {
+        /// The number of consecutive failures
+        failures: u32,
+    },
+    /// Calls fail fast until the deadline.
+    Open {
+        /// When to let a probe through
+        until: Instant,
+    },
+    /// A single probe is in flight, other calls fail fast. Another probe is
+    /// let through after the deadline, in case the probe is cancelled.
+    HalfOpen {
+        /// When to let another probe through
+        until: Instant,
+    },
+}
+
+/// A backend that short-circuits calls to the inner backend after repeated
+/// failures, so that an outage does not stall every cache miss.
+///
+/// While the breaker is open, calls fail with [`StorageError::Unavailable`]
+/// without touching the inner backend. After
+/// [`CircuitBreakerPolicy::open_duration`], one call is let through to probe
+/// the backend, and the breaker is closed again if it succeeds.
+#[derive(Debug)]
+pub struct CircuitBreakerBackend<B: Backend> {
+    /// The inner backend
+    inner: B,
+    /// The breaker policy
+    policy: CircuitBreakerPolicy,
+    /// The breaker state
+    state: Mutex<BreakerState>,
+}
+
+impl<B: Backend> CircuitBreakerBackend<B> {
+    /// Creates a new `CircuitBreakerBackend` wrapping `inner`.
+    #[must_use]
+    pub fn new(inner: B, policy: CircuitBreakerPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        }
+    }
+
+    /// Returns whether the breaker is open, i.e. calls are short-circuited.
+    #[allow(dead_code)]
+    #[must_use]
+    pub fn is_open(&self) -> bool {
+        !matches!(*self.state.lock(), BreakerState::Closed { .. })
+    }
+
+    /// Checks whether a call may go to the inner backend.
+    fn acquire(&self) -> StorageResult<()> {
+        let mut state = self.state.lock();
+        match *state {
+            BreakerState::Closed { .. } => Ok(()),
+            BreakerState::Open { until } | BreakerState::HalfOpen { until }
+                if Instant::now() >= until =>
+            {
+                *state = BreakerState::HalfOpen {
+                    until: Instant::now() + self.policy.open_duration,
+                };
+                Ok(())
+            }
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => {
+                Err(StorageError::Unavailable(
+                    "the circuit breaker is open after repeated backend failures".to_owned(),
+                ))
+            }
+        }
+    }
+
+    /// Updates the breaker state with the result of a call.
+    fn record<T>(&self, result: &StorageResult<T>) {
+        let failed = match *result {
+            Ok(_) => false,
+            // Terminal errors, e.g. permission denied, mean the backend is
+            // reachable.
+            Err(ref e) => e.is_retryable() || matches!(*e, StorageError::Unavailable(_)),
+        };
+
+        let mut state = self.state.lock();
+        let next = match (*state, failed) {
+            (BreakerState::HalfOpen { .. }, false) => {
+                info!("The backend is recovered, close the circuit breaker");
+                BreakerState::Closed { failures: 0 }
+            }
+            (BreakerState::Closed { .. }, false) => BreakerState::Closed { failures: 0 },
+            (BreakerState::Closed { failures }, true)
+                if failures.saturating_add(1) < self.policy.failure_threshold =>
+            {
+                BreakerState::Closed {
+                    failures: failures.saturating_add(1),
+                }
+            }
+            (BreakerState::Closed { .. } | BreakerState::HalfOpen { .. }, true) => {
+                warn!(
+                    "The backend keeps failing, open the circuit breaker for {:?}",
+                    self.policy.open_duration
+                );
+                BreakerState::Open {
+                    until: Instant::now() + self.policy.open_duration,
+                }
+            }
+            // Calls are not let through while open.
+            (BreakerState::Open { .. }, _) => *state,
+        };
+        *state = next;
+    }
+}
+
+#[async_trait]
+impl<B: Backend> Backend for CircuitBreakerBackend<B> {
+    #[inline]
+    async fn read(&self, path: &str, buf: &mut [u8]) -> StorageResult<usize> {
+        self.acquire()?;
+        let result = self.inner.read(path, buf).await;
+        self.record(&result);
+        result
+    }
+
+    #[inline]
+    async fn write(&self, path: &str, buf: &[u8]) -> StorageResult<()> {
+        self.acquire()?;
+        let result = self.inner.write(path, buf).await;
+        self.record(&result);
+        result
+    }
+
+    #[inline]
+    async fn remove(&self, path: &str) -> StorageResult<()> {
+        self.acquire()?;
+        let result = self.inner.remove(path).await;
+        self.record(&result);
+        result
+    }
+
+    #[inline]
+    async fn remove_all(&self, prefix: &str) -> StorageResult<()> {
+        self.acquire()?;
+        let result = self.inner.remove_all(prefix).await;
+        self.record(&result);
+        result
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use std::io;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+    use super::*;
+
+    /// A backend that times out while it is down.
+    #[derive(Debug, Default)]
+    struct OutageBackend {
+        /// Whether the backend is down
+        down: AtomicBool,
+        /// The number of calls reaching the backend
+        calls: AtomicU32,
+    }
+
+    impl OutageBackend {
+        fn call(&self) -> StorageResult<()> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.down.load(Ordering::SeqCst) {
+                return Err(io::Error::from(io::ErrorKind::TimedOut).into());
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Backend for OutageBackend {
+        async fn read(&self, _path: &str, _buf: &mut [u8]) -> StorageResult<usize> {
+            self.call().map(|()| 0)
+        }
+
+        async fn write(&self, _path: &str, _buf: &[u8]) -> StorageResult<()> {
+            self.call()
+        }
+
+        async fn remove(&self, _path: &str) -> StorageResult<()> {
+            self.call()
+        }
+
+        async fn remove_all(&self, _prefix: &str) -> StorageResult<()> {
+            self.call()
+        }
+    }
+
+    const OPEN_DURATION: Duration = Duration::from_millis(50);
+
+    fn test_breaker() -> CircuitBreakerBackend<OutageBackend> {
+        CircuitBreakerBackend::new(
+            OutageBackend::default(),
+            CircuitBreakerPolicy {
+                failure_threshold: 2,
+                open_duration: OPEN_DURATION,
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_open_and_recover() {
+        let backend = test_breaker();
+        backend.write("a", b"data").await.unwrap();
+
+        backend.inner.down.store(true, Ordering::SeqCst);
+        for _ in 0_u32..2 {
+            let err = backend.write("a", b"data").await.unwrap_err();
+            assert!(matches!(err, StorageError::StdIoError(_)), "err = {err:?}");
+        }
+        assert!(backend.is_open());
+
+        // Short-circuited without reaching the backend.
+        let mut buf = [0_u8; 4];
+        let err = backend.read("a", &mut buf).await.unwrap_err();
+        assert!(matches!(err, StorageError::Unavailable(_)), "err = {err:?}");
+        assert_eq!(backend.inner.calls.load(Ordering::SeqCst), 3);
+
+        // The probe succeeds after the backend recovers.
+        backend.inner.down.store(false, Ordering::SeqCst);
+        tokio::time::sleep(OPEN_DURATION).await;
+        backend.read("a", &mut buf).await.unwrap();
+        assert!(!backend.is_open());
+        backend.write("a", b"data").await.unwrap();
+        assert_eq!(backend.inner.calls.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn test_failed_probe() {
+        let backend = test_breaker();
+        backend.inner.down.store(true, Ordering::SeqCst);
+        for _ in 0_u32..2 {
+            backend.remove("a").await.unwrap_err();
+        }
+        assert!(backend.is_open());
+
+        // A failed probe opens the breaker again.
+        tokio::time::sleep(OPEN_DURATION).await;
+        let err = backend.remove("a").await.unwrap_err();
+        assert!(matches!(err, StorageError::StdIoError(_)), "err = {err:?}");
+        let err = backend.remove("a").await.unwrap_err();
+        assert!(matches!(err, StorageError::Unavailable(_)), "err = {err:?}");
+        assert_eq!(backend.inner.calls.load(Ordering::SeqCst), 3);
+    }
+}
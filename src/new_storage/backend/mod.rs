@@ -1,14 +1,22 @@
 //! Backend related modules.
 
 pub mod backend_impl;
+pub mod circuit_breaker;
 pub mod memory_backend;
+pub mod retry;
 
 use std::fmt::Debug;
 
 use async_trait::async_trait;
 pub use backend_impl::{BackendBuilder, BackendImpl};
+pub use circuit_breaker::{CircuitBreakerBackend, CircuitBreakerPolicy};
+pub use retry::{RetryBackend, RetryPolicy};
 
-use super::error::StorageResult;
+use super::error::{StorageError, StorageResult};
+
+/// The path of the object read by [`Backend::health_check`], which need not
+/// exist.
+const HEALTH_CHECK_PATH: &str = ".datenlord_health_check";
 
 /// The `Backend` trait represents a backend storage system.
 #[async_trait]
@@ -21,6 +29,24 @@ pub trait Backend: Debug + Send + Sync {
     async fn remove(&self, path: &str) -> StorageResult<()>;
     /// Removes data with the specified prefix (usually a directory)
     async fn remove_all(&self, prefix: &str) -> StorageResult<()>;
+
+    /// Checks that the storage system is reachable, by reading a probe object.
+    ///
+    /// The check is read-only, so it works with read-only credentials and
+    /// leaves nothing behind. A missing probe object is not an error, any
+    /// other failure is reported as [`StorageError::Unavailable`].
+    async fn health_check(&self) -> StorageResult<()> {
+        let mut buf = [0_u8; 1];
+        match self.read(HEALTH_CHECK_PATH, &mut buf).await {
+            Ok(_) => Ok(()),
+            Err(StorageError::OpenDalError(ref e)) if e.kind() == opendal::ErrorKind::NotFound => {
+                Ok(())
+            }
+            Err(e) => Err(StorageError::Unavailable(format!(
+                "failed to read the probe object: {e}"
+            ))),
+        }
+    }
 }
 
 /// Test backend.
@@ -38,3 +64,89 @@ async fn test_backend(backend: impl Backend) {
     let res = backend.read(path, &mut buf).await.unwrap();
     assert_eq!(res, 0);
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use std::io;
+    use std::time::Duration;
+
+    use super::memory_backend::MemoryBackend;
+    use super::*;
+
+    /// A backend whose every operation fails, as if the remote is unreachable.
+    #[derive(Debug)]
+    struct UnreachableBackend;
+
+    #[async_trait]
+    impl Backend for UnreachableBackend {
+        async fn read(&self, _path: &str, _buf: &mut [u8]) -> StorageResult<usize> {
+            Err(io::Error::from(io::ErrorKind::ConnectionRefused).into())
+        }
+
+        async fn write(&self, _path: &str, _buf: &[u8]) -> StorageResult<()> {
+            Err(io::Error::from(io::ErrorKind::ConnectionRefused).into())
+        }
+
+        async fn remove(&self, _path: &str) -> StorageResult<()> {
+            Err(io::Error::from(io::ErrorKind::ConnectionRefused).into())
+        }
+
+        async fn remove_all(&self, _prefix: &str) -> StorageResult<()> {
+            Err(io::Error::from(io::ErrorKind::ConnectionRefused).into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_health_check() {
+        let backend = MemoryBackend::new(Duration::from_millis(0));
+        backend.health_check().await.unwrap();
+
+        // Nothing is written by the check.
+        let mut buf = vec![0_u8; 16];
+        let size = backend.read(HEALTH_CHECK_PATH, &mut buf).await.unwrap();
+        assert_eq!(size, 0);
+    }
+
+    /// A backend rejecting writes as with read-only credentials, and failing
+    /// to read missing objects like S3 does.
+    #[derive(Debug)]
+    struct ReadOnlyBackend;
+
+    #[async_trait]
+    impl Backend for ReadOnlyBackend {
+        async fn read(&self, _path: &str, _buf: &mut [u8]) -> StorageResult<usize> {
+            Err(opendal::Error::new(opendal::ErrorKind::NotFound, "not found").into())
+        }
+
+        async fn write(&self, _path: &str, _buf: &[u8]) -> StorageResult<()> {
+            Err(io::Error::from(io::ErrorKind::PermissionDenied).into())
+        }
+
+        async fn remove(&self, _path: &str) -> StorageResult<()> {
+            Err(io::Error::from(io::ErrorKind::PermissionDenied).into())
+        }
+
+        async fn remove_all(&self, _prefix: &str) -> StorageResult<()> {
+            Err(io::Error::from(io::ErrorKind::PermissionDenied).into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_health_check_read_only() {
+        ReadOnlyBackend.health_check().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_health_check_unreachable() {
+        let err = UnreachableBackend.health_check().await.unwrap_err();
+        assert!(matches!(err, StorageError::Unavailable(_)), "err = {err:?}");
+
+        let message = err.to_string();
+        assert!(
+            message.starts_with("The storage backend is unavailable"),
+            "message = {message}"
+        );
+        assert!(message.contains("refused"), "message = {message}");
+    }
+}
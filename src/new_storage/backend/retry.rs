@@ -0,0 +1,310 @@
+//! A backend wrapper that retries transient errors with exponential backoff.
+
+use std::future::Future;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use clippy_utilities::{Cast, OverflowArithmetic};
+use rand::Rng;
+use tracing::warn;
+
+use super::{Backend, StorageResult};
+
+/// The policy to retry transient backend errors.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one
+    pub max_attempts: u32,
+    /// The delay before the first retry, doubled on every following retry
+    pub base_delay: Duration,
+    /// The upper bound of the delay between two attempts
+    pub max_delay: Duration,
+    /// Whether to randomize the delay, so that retries from different nodes
+    /// are not synchronized
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before the `retry`-th retry, starting from 0.
+    ///
+    /// With jitter enabled, the delay is picked uniformly from the upper half
+    /// of the backoff, i.e. `[backoff / 2, backoff]`.
+    #[must_use]
+    pub fn delay(&self, retry: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .checked_mul(1_u32.checked_shl(retry).unwrap_or(u32::MAX))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+        if !self.jitter {
+            return backoff;
+        }
+
+        let nanos: u64 = backoff.as_nanos().cast();
+        let half = nanos.overflow_shr(1);
+        Duration::from_nanos(rand::thread_rng().gen_range(half..=nanos))
+    }
+}
+
+/// A backend that retries the operations of the inner backend on transient
+/// errors, according to a [`RetryPolicy`].
+#[derive(Debug)]
+pub struct RetryBackend<B: Backend> {
+    /// The inner backend
+    inner: B,
+    /// The retry policy
+    policy: RetryPolicy,
+}
+
+impl<B: Backend> RetryBackend<B> {
+    /// Creates a new `RetryBackend` wrapping `inner`.
+    #[must_use]
+    pub fn new(inner: B, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    /// Runs `op` until it succeeds, fails with a terminal error, or the
+    /// maximum number of attempts is reached.
+    async fn retry<T, F, Fut>(&self, name: &str, path: &str, mut op: F) -> StorageResult<T>
+    where
+        T: Send,
+        F: FnMut() -> Fut + Send,
+        Fut: Future<Output = StorageResult<T>> + Send,
+    {
+        self.retry_with(name, path, (), |()| {
+            let fut = op();
+            async move { ((), fut.await) }
+        })
+        .await
+    }
+
+    /// Like [`RetryBackend::retry`], but `state` is passed to every attempt
+    /// and handed back with its result, e.g. a buffer that a closure cannot
+    /// reborrow.
+    async fn retry_with<S, T, F, Fut>(
+        &self,
+        name: &str,
+        path: &str,
+        mut state: S,
+        mut op: F,
+    ) -> StorageResult<T>
+    where
+        S: Send,
+        T: Send,
+        F: FnMut(S) -> Fut + Send,
+        Fut: Future<Output = (S, StorageResult<T>)> + Send,
+    {
+        let mut retry = 0_u32;
+        loop {
+            let (next_state, result) = op(state).await;
+            state = next_state;
+            match result {
+                Ok(res) => return Ok(res),
+                Err(e)
+                    if e.is_retryable() && retry.saturating_add(1) < self.policy.max_attempts =>
+                {
+                    let delay = self.policy.delay(retry);
+                    warn!(
+                        "Backend {name} on {path} failed with a transient error: {e}, \
+                            retry in {delay:?}"
+                    );
+                    tokio::time::sleep(delay).await;
+                    retry = retry.saturating_add(1);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<B: Backend> Backend for RetryBackend<B> {
+    #[inline]
+    async fn read(&self, path: &str, buf: &mut [u8]) -> StorageResult<usize> {
+        self.retry_with("read", path, buf, |buf| async move {
+            let res = self.inner.read(path, &mut *buf).await;
+            (buf, res)
+        })
+        .await
+    }
+
+    #[inline]
+    async fn write(&self, path: &str, buf: &[u8]) -> StorageResult<()> {
+        self.retry("write", path, || self.inner.write(path, buf))
+            .await
+    }
+
+    #[inline]
+    async fn remove(&self, path: &str) -> StorageResult<()> {
+        self.retry("remove", path, || self.inner.remove(path)).await
+    }
+
+    #[inline]
+    async fn remove_all(&self, prefix: &str) -> StorageResult<()> {
+        self.retry("remove_all", prefix, || self.inner.remove_all(prefix))
+            .await
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use std::io;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+    use crate::new_storage::StorageError;
+
+    /// A backend that fails the first `failures` reads and writes with
+    /// `kind`.
+    #[derive(Debug)]
+    struct FlakyBackend {
+        /// The kind of the injected error
+        kind: io::ErrorKind,
+        /// The number of reads and writes to fail
+        failures: u32,
+        /// The number of reads and writes attempted
+        attempts: AtomicU32,
+    }
+
+    impl FlakyBackend {
+        fn new(kind: io::ErrorKind, failures: u32) -> Self {
+            Self {
+                kind,
+                failures,
+                attempts: AtomicU32::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Backend for FlakyBackend {
+        async fn read(&self, _path: &str, buf: &mut [u8]) -> StorageResult<usize> {
+            let attempt = self.attempts.fetch_add(1, Ordering::SeqCst);
+            if attempt < self.failures {
+                return Err(io::Error::from(self.kind).into());
+            }
+            buf.fill(1);
+            Ok(buf.len())
+        }
+
+        async fn write(&self, _path: &str, _buf: &[u8]) -> StorageResult<()> {
+            let attempt = self.attempts.fetch_add(1, Ordering::SeqCst);
+            if attempt < self.failures {
+                return Err(io::Error::from(self.kind).into());
+            }
+            Ok(())
+        }
+
+        async fn remove(&self, _path: &str) -> StorageResult<()> {
+            Ok(())
+        }
+
+        async fn remove_all(&self, _prefix: &str) -> StorageResult<()> {
+            Ok(())
+        }
+    }
+
+    fn test_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(10),
+            jitter: true,
+        }
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy {
+            jitter: false,
+            ..test_policy()
+        };
+        assert_eq!(policy.delay(0), Duration::from_millis(1));
+        assert_eq!(policy.delay(2), Duration::from_millis(4));
+        assert_eq!(policy.delay(10), Duration::from_millis(10));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_millis(10));
+
+        let policy = test_policy();
+        for retry in 0..8 {
+            let delay = policy.delay(retry);
+            let backoff = RetryPolicy {
+                jitter: false,
+                ..policy
+            }
+            .delay(retry);
+            assert!(
+                delay >= backoff / 2 && delay <= backoff,
+                "delay = {delay:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient() {
+        let backend =
+            RetryBackend::new(FlakyBackend::new(io::ErrorKind::TimedOut, 2), test_policy());
+        backend.write("a", b"data").await.unwrap();
+        assert_eq!(backend.inner.attempts.load(Ordering::SeqCst), 3);
+
+        // Give up after `max_attempts`.
+        let backend =
+            RetryBackend::new(FlakyBackend::new(io::ErrorKind::TimedOut, 3), test_policy());
+        let err = backend.write("a", b"data").await.unwrap_err();
+        assert!(matches!(err, StorageError::StdIoError(_)), "err = {err:?}");
+        assert_eq!(backend.inner.attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_read() {
+        let backend =
+            RetryBackend::new(FlakyBackend::new(io::ErrorKind::TimedOut, 2), test_policy());
+        let mut buf = vec![0_u8; 4];
+        let size = backend.read("a", &mut buf).await.unwrap();
+        assert_eq!(size, 4);
+        assert_eq!(buf, [1; 4]);
+        assert_eq!(backend.inner.attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_opendal_error_retryable() {
+        let retryable = |err: opendal::Error| StorageError::from(err).is_retryable();
+        assert!(retryable(opendal::Error::new(
+            opendal::ErrorKind::RateLimited,
+            "slow down"
+        )));
+        assert!(retryable(
+            opendal::Error::new(opendal::ErrorKind::Unexpected, "internal error").set_temporary()
+        ));
+        assert!(!retryable(opendal::Error::new(
+            opendal::ErrorKind::PermissionDenied,
+            "access denied"
+        )));
+        assert!(!retryable(opendal::Error::new(
+            opendal::ErrorKind::NotFound,
+            "not found"
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_terminal_error() {
+        let backend = RetryBackend::new(
+            FlakyBackend::new(io::ErrorKind::PermissionDenied, 1),
+            test_policy(),
+        );
+        let err = backend.write("a", b"data").await.unwrap_err();
+        assert!(matches!(err, StorageError::StdIoError(_)), "err = {err:?}");
+        assert_eq!(backend.inner.attempts.load(Ordering::SeqCst), 1);
+    }
+}
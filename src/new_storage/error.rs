@@ -1,5 +1,7 @@
 //! The storage related error.
 
+use std::io::ErrorKind;
+
 use anyhow::anyhow;
 use nix::errno::Errno;
 use thiserror::Error;
@@ -21,11 +23,54 @@ pub enum StorageError {
     /// An error caused by [`opendal::Error`]
     #[error("{0}")]
     OpenDalError(#[from] opendal::Error),
+    /// Too many handles are opened on a single file.
+    #[error("Too many open handles on inode {ino}, the limit is {limit}")]
+    TooManyInodeHandles {
+        /// The inode number
+        ino: u64,
+        /// The maximum number of handles on a single inode
+        limit: usize,
+    },
+    /// Too many handles are opened on the file system.
+    #[error("Too many open handles, the limit is {limit}")]
+    TooManyHandles {
+        /// The maximum number of handles in total
+        limit: usize,
+    },
+    /// The storage backend is unreachable or misconfigured.
+    #[error("The storage backend is unavailable: {0}")]
+    Unavailable(String),
     /// A internal storage error.
     #[error("{0}")]
     Internal(#[from] anyhow::Error),
 }
 
+impl StorageError {
+    /// Returns whether the error is transient, e.g. throttling or a server
+    /// side failure, so the operation may succeed if retried.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        match *self {
+            StorageError::OpenDalError(ref e) => {
+                e.is_temporary() || e.kind() == opendal::ErrorKind::RateLimited
+            }
+            StorageError::StdIoError(ref e) => matches!(
+                e.kind(),
+                ErrorKind::TimedOut
+                    | ErrorKind::Interrupted
+                    | ErrorKind::WouldBlock
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+            ),
+            StorageError::OutOfMemory
+            | StorageError::TooManyInodeHandles { .. }
+            | StorageError::TooManyHandles { .. }
+            | StorageError::Unavailable(_)
+            | StorageError::Internal(_) => false,
+        }
+    }
+}
+
 impl From<StorageError> for DatenLordError {
     fn from(value: StorageError) -> Self {
         match value {
@@ -33,6 +78,18 @@ impl From<StorageError> for DatenLordError {
                 source: anyhow::Error::new(Errno::EIO),
                 context: vec!["Cache is out of memory.".to_owned()],
             },
+            StorageError::TooManyInodeHandles { .. } => DatenLordError::InternalErr {
+                source: anyhow::Error::new(Errno::EMFILE),
+                context: vec![value.to_string()],
+            },
+            StorageError::TooManyHandles { .. } => DatenLordError::InternalErr {
+                source: anyhow::Error::new(Errno::ENFILE),
+                context: vec![value.to_string()],
+            },
+            StorageError::Unavailable(reason) => DatenLordError::InternalErr {
+                source: anyhow::Error::new(Errno::EIO),
+                context: vec![format!("The storage backend is unavailable: {reason}")],
+            },
             StorageError::StdIoError(e) => {
                 let errno = e.raw_os_error();
                 DatenLordError::InternalErr {
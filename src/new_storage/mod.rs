@@ -14,7 +14,10 @@ mod storage_manager;
 mod storage_trait;
 mod utils;
 
-pub use backend::{Backend, BackendBuilder, BackendImpl};
+pub use backend::{
+    Backend, BackendBuilder, BackendImpl, CircuitBreakerBackend, CircuitBreakerPolicy,
+    RetryBackend, RetryPolicy,
+};
 pub use block::Block;
 #[cfg(test)]
 pub use block::BLOCK_SIZE;
@@ -1,8 +1,9 @@
 //! The file handle implementation
 
-use std::collections::hash_map::DefaultHasher;
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
-use std::sync::atomic::AtomicU32;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use bytes::Bytes;
@@ -111,15 +112,40 @@ struct WriteTask {
     block: Arc<RwLock<Block>>,
 }
 
-/// Write a block back to the backend.
-async fn write_back_block(task: Arc<WriteTask>) -> StorageResult<()> {
+/// The pending write tasks of the write back worker, coalesced by block id.
+///
+/// Writes to the same block are merged into a single backend write, the count
+/// records how many pins the merged writes hold on the block.
+type PendingWrites = HashMap<u64, (Arc<WriteTask>, usize)>;
+
+/// Write a block back to the backend, and release `pins` pins of the block.
+///
+/// The pins are kept if the write fails, so that the dirty block is not
+/// evicted before the write is retried.
+async fn write_back_block(task: Arc<WriteTask>, pins: usize) -> StorageResult<()> {
+    write_block_content(&task).await?;
+
+    let key = CacheKey {
+        ino: task.ino,
+        block_id: task.block_id,
+    };
+    let mut cache = task.cache.lock();
+    for _ in 0..pins {
+        cache.unpin(&key);
+    }
+
+    Ok(())
+}
+
+/// Write the content of a dirty block to the backend, and mark it clean.
+async fn write_block_content(task: &WriteTask) -> StorageResult<()> {
     let path = format_path(task.ino, task.block_id);
     loop {
         let (content, version) = {
             let block = task.block.read();
             if !block.dirty() {
                 // The block has been flushed previously, skip
-                return Ok(());
+                break;
             }
             let content = Bytes::copy_from_slice(block.as_ref());
             let version = block.version();
@@ -140,27 +166,35 @@ async fn write_back_block(task: Arc<WriteTask>) -> StorageResult<()> {
             }
             block.set_dirty(false);
         }
-        {
-            task.cache.lock().unpin(&CacheKey {
-                ino: task.ino,
-                block_id: task.block_id,
-            });
-            break;
-        }
+        break;
     }
 
     Ok(())
 }
 
+/// Add a write task to the pending writes, merging it with the pending write
+/// of the same block if any.
+fn add_pending_write(tasks: &mut PendingWrites, task: Arc<WriteTask>) {
+    match tasks.entry(task.block_id) {
+        Entry::Occupied(mut entry) => entry.get_mut().1 += 1,
+        Entry::Vacant(entry) => {
+            entry.insert((task, 1));
+        }
+    }
+}
+
 /// Write the blocks to the backend storage system concurrently.
-async fn write_blocks(tasks: &Vec<Arc<WriteTask>>) -> Option<StorageError> {
+///
+/// The written blocks are removed from `tasks`, while the failed ones are kept
+/// to be retried by the next write back.
+async fn write_blocks(tasks: &mut PendingWrites) -> Option<StorageError> {
     let mut handles = Vec::new();
     let mut result = None;
-    for task in tasks {
-        let handle = tokio::spawn(write_back_block(Arc::clone(task)));
-        handles.push(handle);
+    for (&block_id, &(ref task, pins)) in tasks.iter() {
+        let handle = tokio::spawn(write_back_block(Arc::clone(task), pins));
+        handles.push((block_id, handle));
     }
-    for handle in handles {
+    for (block_id, handle) in handles {
         match handle.await {
             Err(e) => {
                 result = Some(StorageError::Internal(e.into()));
@@ -168,7 +202,9 @@ async fn write_blocks(tasks: &Vec<Arc<WriteTask>>) -> Option<StorageError> {
             Ok(Err(e)) => {
                 result = Some(e);
             }
-            _ => {}
+            Ok(Ok(())) => {
+                tasks.remove(&block_id);
+            }
         }
     }
     result
@@ -179,34 +215,30 @@ async fn write_blocks(tasks: &Vec<Arc<WriteTask>>) -> Option<StorageError> {
 async fn write_back_work(mut write_back_receiver: Receiver<Task>) {
     //  Create a timer to flush the cache every 200ms.
     let mut interval = tokio::time::interval(std::time::Duration::from_millis(200));
-    let mut tasks = Vec::new();
-    let mut result = None;
+    // The failed writes stay in `tasks` until they succeed, so an error is
+    // reported by every flush until then.
+    let mut tasks = PendingWrites::new();
     loop {
         tokio::select! {
             Some(task) = write_back_receiver.recv() => {
                 match task {
                     Task::Pending(task) => {
-                        tasks.push(task);
+                        add_pending_write(&mut tasks, task);
                         if tasks.len() >= 10 {
-                            let res = write_blocks(&tasks).await;
-                            if let Some(e) = res {
-                                result.get_or_insert(e);
-                            }
-                            tasks.clear();
+                            write_blocks(&mut tasks).await;
                         }
                     }
                     Task::Flush(tx) => {
-                        let res = write_blocks(&tasks).await;
-                        tasks.clear();
-                        let res = result.take().or(res);
+                        let res = write_blocks(&mut tasks).await;
                         if let Err(Some(e)) = tx.send(res) {
                             error!("Failed to send storage error back to `Writer`, the error is {e}.");
                         }
                     }
                     Task::Finish(tx) => {
-                        let res = write_blocks(&tasks).await;
-                        tasks.clear();
-                        let res = result.take().or(res);
+                        let res = write_blocks(&mut tasks).await;
+                        if !tasks.is_empty() {
+                            error!("{} dirty blocks are not written back on close.", tasks.len());
+                        }
                         if let Err(Some(e)) = tx.send(res) {
                             error!("Failed to send storage error back to `Writer`, the error is {e}.");
                         }
@@ -215,8 +247,7 @@ async fn write_back_work(mut write_back_receiver: Receiver<Task>) {
                 }
             }
             _ = interval.tick() => {
-                write_blocks(&tasks).await;
-                tasks.clear();
+                write_blocks(&mut tasks).await;
             }
         }
     }
@@ -498,6 +529,12 @@ impl FileHandle {
         self.fh
     }
 
+    /// Returns the inode number of the file.
+    #[must_use]
+    pub fn ino(&self) -> u64 {
+        self.inner.ino
+    }
+
     /// Gets a reader of this file handle.
     ///
     /// # Panic
@@ -564,12 +601,26 @@ impl FileHandle {
 /// Number of handle shards.
 const HANDLE_SHARD_NUM: usize = 100;
 
+/// The default maximum number of handles opened on a single inode.
+pub const DEFAULT_MAX_HANDLES_PER_INODE: usize = 1024;
+
+/// The default maximum number of handles opened on the file system.
+pub const DEFAULT_MAX_HANDLES: usize = 64 * 1024;
+
 /// The `Handles` struct represents a collection of file handles.
 /// It uses sharding to avoid lock contention.
 #[derive(Debug)]
 pub struct Handles {
     /// Use shard to avoid lock contention
-    handles: [Arc<RwLock<Vec<FileHandle>>>; HANDLE_SHARD_NUM],
+    shards: [Arc<RwLock<Vec<FileHandle>>>; HANDLE_SHARD_NUM],
+    /// The number of handles opened on each inode
+    per_inode: Mutex<HashMap<u64, usize>>,
+    /// The number of handles opened in total
+    total: AtomicUsize,
+    /// The maximum number of handles opened on a single inode
+    max_per_inode: usize,
+    /// The maximum number of handles opened in total
+    max_total: usize,
 }
 
 impl Default for Handles {
@@ -579,18 +630,31 @@ impl Default for Handles {
 }
 
 impl Handles {
-    /// Creates a new `Handles` instance.
+    /// Creates a new `Handles` instance with the default limits.
     #[must_use]
     #[inline]
     pub fn new() -> Self {
-        let mut handles: Vec<_> = Vec::with_capacity(HANDLE_SHARD_NUM);
+        Self::with_limits(DEFAULT_MAX_HANDLES_PER_INODE, DEFAULT_MAX_HANDLES)
+    }
+
+    /// Creates a new `Handles` instance, which allows at most `max_per_inode`
+    /// handles on a single inode and `max_total` handles in total.
+    #[must_use]
+    pub fn with_limits(max_per_inode: usize, max_total: usize) -> Self {
+        let mut shards: Vec<_> = Vec::with_capacity(HANDLE_SHARD_NUM);
         for _ in 0..HANDLE_SHARD_NUM {
-            handles.push(Arc::new(RwLock::new(Vec::new())));
+            shards.push(Arc::new(RwLock::new(Vec::new())));
         }
-        let handles: [_; HANDLE_SHARD_NUM] = handles.try_into().unwrap_or_else(|_| {
+        let shards: [_; HANDLE_SHARD_NUM] = shards.try_into().unwrap_or_else(|_| {
             unreachable!("The length should match.");
         });
-        Handles { handles }
+        Handles {
+            shards,
+            per_inode: Mutex::new(HashMap::new()),
+            total: AtomicUsize::new(0),
+            max_per_inode,
+            max_total,
+        }
     }
 
     /// Returns the shard index for the given file handle.
@@ -603,16 +667,38 @@ impl Handles {
     /// Gets a shard of the fh.
     fn get_shard(&self, fh: u64) -> &Arc<RwLock<Vec<FileHandle>>> {
         let idx = Self::hash(fh);
-        self.handles
+        self.shards
             .get(idx)
             .unwrap_or_else(|| unreachable!("The array is ensured to be long enough."))
     }
 
     /// Adds a file handle to the collection.
-    pub fn add_handle(&self, fh: FileHandle) {
+    ///
+    /// Returns an error if the handle limit of the inode or the file system is
+    /// reached.
+    pub fn add_handle(&self, fh: FileHandle) -> StorageResult<()> {
+        {
+            let mut per_inode = self.per_inode.lock();
+            let count = per_inode.get(&fh.ino()).copied().unwrap_or(0);
+            if count >= self.max_per_inode {
+                return Err(StorageError::TooManyInodeHandles {
+                    ino: fh.ino(),
+                    limit: self.max_per_inode,
+                });
+            }
+            if self.total.load(Ordering::Acquire) >= self.max_total {
+                return Err(StorageError::TooManyHandles {
+                    limit: self.max_total,
+                });
+            }
+            per_inode.insert(fh.ino(), count + 1);
+            self.total.fetch_add(1, Ordering::AcqRel);
+        }
+
         let shard = self.get_shard(fh.fh());
         let mut shard_lock = shard.write();
         shard_lock.push(fh);
+        Ok(())
     }
 
     /// Removes a file handle from the collection.'
@@ -620,10 +706,21 @@ impl Handles {
     pub fn remove_handle(&self, fh: u64) -> Option<FileHandle> {
         let shard = self.get_shard(fh);
         let mut shard_lock = shard.write();
-        shard_lock
+        let handle = shard_lock
             .iter()
             .position(|h| h.fh() == fh)
-            .map(|pos| shard_lock.remove(pos))
+            .map(|pos| shard_lock.remove(pos))?;
+        drop(shard_lock);
+
+        let mut per_inode = self.per_inode.lock();
+        if let Entry::Occupied(mut entry) = per_inode.entry(handle.ino()) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+        self.total.fetch_sub(1, Ordering::AcqRel);
+        Some(handle)
     }
 
     /// Returns a file handle from the collection.
@@ -634,13 +731,35 @@ impl Handles {
         let fh = shard_lock.iter().find(|h| h.fh() == fh)?;
         Some(fh.clone())
     }
+
+    /// Returns the number of open file handles.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.total.load(Ordering::Acquire)
+    }
+
+    /// Returns whether there is no open file handle.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 #[cfg(test)]
 #[allow(clippy::unwrap_used)]
 mod tests {
+    use std::io;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+
+    use nix::errno::Errno;
+
     use super::*;
+    use crate::common::error::DatenLordError;
     use crate::new_storage::backend::backend_impl::tmp_fs_backend;
+    use crate::new_storage::backend::memory_backend::MemoryBackend;
     use crate::new_storage::block::BLOCK_SIZE;
 
     const IO_SIZE: usize = 128 * 1024;
@@ -660,11 +779,208 @@ mod tests {
             flag: OpenFlag::ReadAndWrite,
             inner: file_handle,
         };
-        handles.add_handle(file_handle.clone());
+        handles.add_handle(file_handle.clone()).unwrap();
         let buf = vec![b'1', b'2', b'3', b'4'];
         file_handle.write(0, &buf).await.unwrap();
         let read_buf = file_handle.read(0, 4).await.unwrap();
         assert_eq!(read_buf, buf);
         file_handle.flush().await.unwrap();
     }
+
+    /// A backend counting the writes sent to it.
+    #[derive(Debug)]
+    struct CountingBackend {
+        /// The inner backend.
+        inner: MemoryBackend,
+        /// The number of writes.
+        writes: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Backend for CountingBackend {
+        async fn read(&self, path: &str, buf: &mut [u8]) -> StorageResult<usize> {
+            self.inner.read(path, buf).await
+        }
+
+        async fn write(&self, path: &str, buf: &[u8]) -> StorageResult<()> {
+            self.writes.fetch_add(1, Ordering::SeqCst);
+            self.inner.write(path, buf).await
+        }
+
+        async fn remove(&self, path: &str) -> StorageResult<()> {
+            self.inner.remove(path).await
+        }
+
+        async fn remove_all(&self, prefix: &str) -> StorageResult<()> {
+            self.inner.remove_all(prefix).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_writes() {
+        let cache = Arc::new(Mutex::new(MemoryCache::new(100, BLOCK_SIZE)));
+        let backend = Arc::new(CountingBackend {
+            inner: MemoryBackend::new(Duration::from_millis(0)),
+            writes: AtomicUsize::new(0),
+        });
+        let file_handle = FileHandle::new(
+            1,
+            1,
+            BLOCK_SIZE,
+            Arc::clone(&cache),
+            Arc::<CountingBackend>::clone(&backend),
+            OpenFlag::ReadAndWrite,
+        );
+
+        let buf = vec![b'1'; 1024];
+        for i in 0..3 {
+            file_handle.write(i * 1024, &buf).await.unwrap();
+        }
+        file_handle.flush().await.unwrap();
+        assert_eq!(backend.writes.load(Ordering::SeqCst), 1);
+
+        let mut content = vec![0; BLOCK_SIZE];
+        backend
+            .read(&format_path(1, 0), &mut content)
+            .await
+            .unwrap();
+        assert!(content.iter().take(3 * 1024).all(|&b| b == b'1'));
+
+        // All the pins of the merged writes are released.
+        let block = cache
+            .lock()
+            .fetch(&CacheKey {
+                ino: 1,
+                block_id: 0,
+            })
+            .unwrap();
+        assert_eq!(block.read().pin_count(), 1);
+    }
+
+    /// A backend failing the writes while it is down.
+    #[derive(Debug)]
+    struct FlakyBackend {
+        /// The inner backend.
+        inner: MemoryBackend,
+        /// Whether the backend is down.
+        down: AtomicBool,
+    }
+
+    #[async_trait]
+    impl Backend for FlakyBackend {
+        async fn read(&self, path: &str, buf: &mut [u8]) -> StorageResult<usize> {
+            self.inner.read(path, buf).await
+        }
+
+        async fn write(&self, path: &str, buf: &[u8]) -> StorageResult<()> {
+            if self.down.load(Ordering::SeqCst) {
+                return Err(io::Error::from(io::ErrorKind::TimedOut).into());
+            }
+            self.inner.write(path, buf).await
+        }
+
+        async fn remove(&self, path: &str) -> StorageResult<()> {
+            self.inner.remove(path).await
+        }
+
+        async fn remove_all(&self, prefix: &str) -> StorageResult<()> {
+            self.inner.remove_all(prefix).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_write_back_retried() {
+        let cache = Arc::new(Mutex::new(MemoryCache::new(100, BLOCK_SIZE)));
+        let backend = Arc::new(FlakyBackend {
+            inner: MemoryBackend::new(Duration::from_millis(0)),
+            down: AtomicBool::new(true),
+        });
+        let file_handle = FileHandle::new(
+            1,
+            1,
+            BLOCK_SIZE,
+            Arc::clone(&cache),
+            Arc::<FlakyBackend>::clone(&backend),
+            OpenFlag::ReadAndWrite,
+        );
+        let key = CacheKey {
+            ino: 1,
+            block_id: 0,
+        };
+
+        let buf = vec![b'1'; 1024];
+        for i in 0..3 {
+            file_handle.write(i * 1024, &buf).await.unwrap();
+        }
+        assert!(file_handle.flush().await.is_err());
+
+        // The block is kept dirty and pinned by the failed write.
+        let block = cache.lock().fetch(&key).unwrap();
+        assert!(block.read().dirty());
+        assert!(block.read().pin_count() > 1);
+        cache.lock().unpin(&key);
+
+        // The failed write is retried by the next flush.
+        backend.down.store(false, Ordering::SeqCst);
+        file_handle.flush().await.unwrap();
+        assert!(!block.read().dirty());
+        assert_eq!(block.read().pin_count(), 0);
+
+        let mut content = vec![0; BLOCK_SIZE];
+        backend
+            .read(&format_path(1, 0), &mut content)
+            .await
+            .unwrap();
+        assert!(content.iter().take(3 * 1024).all(|&b| b == b'1'));
+    }
+
+    #[tokio::test]
+    async fn test_handle_limits() {
+        let cache = Arc::new(Mutex::new(MemoryCache::new(100, BLOCK_SIZE)));
+        let backend = Arc::new(MemoryBackend::new(Duration::from_millis(0)));
+        let new_handle = |fh: u64, ino: u64| {
+            FileHandle::new(
+                fh,
+                ino,
+                BLOCK_SIZE,
+                Arc::clone(&cache),
+                Arc::<MemoryBackend>::clone(&backend),
+                OpenFlag::Read,
+            )
+        };
+        let handles = Handles::with_limits(2, 3);
+
+        handles.add_handle(new_handle(1, 1)).unwrap();
+        handles.add_handle(new_handle(2, 1)).unwrap();
+        let err = handles.add_handle(new_handle(3, 1)).unwrap_err();
+        assert!(
+            matches!(err, StorageError::TooManyInodeHandles { ino: 1, limit: 2 }),
+            "err = {err:?}"
+        );
+        assert_errno(err, Errno::EMFILE);
+
+        handles.add_handle(new_handle(4, 2)).unwrap();
+        let err = handles.add_handle(new_handle(5, 3)).unwrap_err();
+        assert!(
+            matches!(err, StorageError::TooManyHandles { limit: 3 }),
+            "err = {err:?}"
+        );
+        assert_errno(err, Errno::ENFILE);
+        assert_eq!(handles.len(), 3);
+
+        // Releasing a handle makes room for a new one.
+        assert!(handles.remove_handle(1).is_some());
+        assert_eq!(handles.len(), 2);
+        handles.add_handle(new_handle(6, 1)).unwrap();
+        assert!(handles.get_handle(3).is_none());
+        assert!(handles.get_handle(5).is_none());
+    }
+
+    /// Asserts that `err` is replied to the kernel as `errno`.
+    fn assert_errno(err: StorageError, errno: Errno) {
+        let DatenLordError::InternalErr { source, .. } = DatenLordError::from(err) else {
+            panic!("storage errors should be internal errors");
+        };
+        assert_eq!(source.root_cause().downcast_ref::<Errno>(), Some(&errno));
+    }
 }
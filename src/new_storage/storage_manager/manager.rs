@@ -4,6 +4,7 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 use clippy_utilities::{Cast, OverflowArithmetic};
+use datenlord::metrics::STORAGE_METRICS;
 use parking_lot::Mutex;
 
 use super::super::policy::LruPolicy;
@@ -32,7 +33,7 @@ impl Storage for StorageManager {
     /// Opens a file with the given inode number and flags, returning a new file
     /// handle.
     #[inline]
-    fn open(&self, ino: u64, fh: u64, flag: OpenFlag) {
+    fn open(&self, ino: u64, fh: u64, flag: OpenFlag) -> StorageResult<()> {
         // Get existing file handle if it exists
         if let Some(handle) = self.handles.get_handle(fh) {
             handle.open();
@@ -45,8 +46,10 @@ impl Storage for StorageManager {
                 Arc::clone(&self.backend),
                 flag,
             );
-            self.handles.add_handle(handle);
+            self.handles.add_handle(handle)?;
+            STORAGE_METRICS.set_open_handles(self.handles.len());
         }
+        Ok(())
     }
 
     /// Reads data from a file specified by the file handle, starting at the
@@ -81,6 +84,7 @@ impl Storage for StorageManager {
             .handles
             .remove_handle(fh)
             .unwrap_or_else(|| panic!("Cannot close a file that is not open."));
+        STORAGE_METRICS.set_open_handles(self.handles.len());
         handle.close().await?;
         Ok(())
     }
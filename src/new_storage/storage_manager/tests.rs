@@ -84,7 +84,7 @@ fn check_data(data: &[u8], user_index: u64) {
 async fn warm_up(storage: Arc<StorageManager>, ino: u64) {
     let flag = OpenFlag::Read;
     let fh = CURRENT_FD.fetch_add(1, Ordering::SeqCst);
-    storage.open(ino, fh, flag);
+    storage.open(ino, fh, flag).unwrap();
     for i in 0..TOTAL_TEST_BLOCKS {
         let buf = storage
             .read(ino, fh, (i * IO_SIZE) as u64, IO_SIZE)
@@ -97,7 +97,7 @@ async fn warm_up(storage: Arc<StorageManager>, ino: u64) {
 async fn seq_read(storage: Arc<StorageManager>, ino: u64) {
     let flag = OpenFlag::Read;
     let fh = CURRENT_FD.fetch_add(1, Ordering::SeqCst);
-    storage.open(ino, fh, flag);
+    storage.open(ino, fh, flag).unwrap();
     for i in 0..TOTAL_TEST_BLOCKS {
         let buf = storage
             .read(10, fh, (i * IO_SIZE) as u64, IO_SIZE)
@@ -112,7 +112,7 @@ async fn seq_read(storage: Arc<StorageManager>, ino: u64) {
 async fn create_a_file(storage: Arc<StorageManager>, ino: u64) {
     let flag = OpenFlag::Write;
     let fh = CURRENT_FD.fetch_add(1, Ordering::SeqCst);
-    storage.open(ino, fh, flag);
+    storage.open(ino, fh, flag).unwrap();
     let start = std::time::Instant::now();
     for i in 0..TOTAL_TEST_BLOCKS {
         let mut content = Vec::new();
@@ -223,7 +223,7 @@ async fn concurrency_read_with_write() {
 async fn scan_worker(storage: Arc<StorageManager>, ino: u64, time: u64) -> usize {
     let flag = OpenFlag::Read;
     let fh = CURRENT_FD.fetch_add(1, Ordering::SeqCst);
-    storage.open(ino, fh, flag);
+    storage.open(ino, fh, flag).unwrap();
     let start = tokio::time::Instant::now();
     let mut i = 0;
     let mut scan_cnt = 0;
@@ -243,7 +243,7 @@ async fn scan_worker(storage: Arc<StorageManager>, ino: u64, time: u64) -> usize
 async fn get_worker(storage: Arc<StorageManager>, ino: u64, time: u64) -> usize {
     let flag = OpenFlag::Read;
     let fh = CURRENT_FD.fetch_add(1, Ordering::SeqCst);
-    storage.open(ino, fh, flag);
+    storage.open(ino, fh, flag).unwrap();
     let start = tokio::time::Instant::now();
 
     // 初始化 Zipfian 分布
@@ -339,7 +339,7 @@ async fn test_truncate() {
     let mut buffer = vec![0; BLOCK_SIZE];
 
     let fh = CURRENT_FD.fetch_add(1, Ordering::SeqCst);
-    storage.open(ino, fh, OpenFlag::ReadAndWrite);
+    storage.open(ino, fh, OpenFlag::ReadAndWrite).unwrap();
     storage.write(ino, fh, 0, &content).await.unwrap();
     storage.close(fh).await.unwrap();
     let size = backend
@@ -402,7 +402,7 @@ async fn test_remove() {
     let mut buffer = vec![0; BLOCK_SIZE];
 
     let fh = CURRENT_FD.fetch_add(1, Ordering::SeqCst);
-    storage.open(ino, fh, OpenFlag::ReadAndWrite);
+    storage.open(ino, fh, OpenFlag::ReadAndWrite).unwrap();
     storage.write(ino, fh, 0, &content).await.unwrap();
     storage.close(fh).await.unwrap();
 
@@ -419,3 +419,29 @@ async fn test_remove() {
         .unwrap();
     assert_eq!(size, 0);
 }
+
+#[tokio::test]
+async fn test_zero_length_io() {
+    let cache = Arc::new(Mutex::new(MemoryCache::new(1024, BLOCK_SIZE)));
+    let backend = Arc::new(MemoryBackend::new(BACKEND_LATENCY));
+    let backend_clone = Arc::clone(&backend);
+    let storage = StorageManager::new(cache, backend_clone, BLOCK_SIZE);
+
+    let ino = 0;
+    let offset: u64 = (BLOCK_SIZE * 3).cast();
+    let mut buffer = vec![0; BLOCK_SIZE];
+
+    let fh = CURRENT_FD.fetch_add(1, Ordering::SeqCst);
+    storage.open(ino, fh, OpenFlag::ReadAndWrite).unwrap();
+    storage.write(ino, fh, offset, &[]).await.unwrap();
+    let content = storage.read(ino, fh, offset, 0).await.unwrap();
+    assert!(content.is_empty());
+    storage.close(fh).await.unwrap();
+
+    // Nothing is written back for a zero-length write
+    let size = backend
+        .read(&format_path(ino, 3), &mut buffer)
+        .await
+        .unwrap();
+    assert_eq!(size, 0);
+}
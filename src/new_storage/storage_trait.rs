@@ -9,7 +9,9 @@ use super::{OpenFlag, StorageResult};
 pub trait Storage {
     /// Opens a file with the given inode number and flags, returning a file
     /// handle.
-    fn open(&self, ino: u64, fh: u64, flag: OpenFlag);
+    ///
+    /// Returns an error if too many handles are opened.
+    fn open(&self, ino: u64, fh: u64, flag: OpenFlag) -> StorageResult<()>;
 
     /// Reads data from a file specified by the inode number and file handle,
     /// starting at the given offset and reading up to `len` bytes.